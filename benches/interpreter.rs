@@ -0,0 +1,103 @@
+// Times the interpreter on programs compiled from the small language, which
+// exercise calls, loops and the relative base far more than the puzzle inputs.
+//
+//     cargo bench --bench interpreter
+
+use std::time::Instant;
+
+use rust_advent_2019::intcode::lang::compile;
+use rust_advent_2019::intcode::observer::Profiler;
+use rust_advent_2019::intcode::{Machine, State};
+
+const WORKLOADS: [(&str, &str, i64); 3] = [
+    (
+        "fib",
+        "fn fib(n) { if n < 2 { return n; } return fib(n - 1) + fib(n - 2); }
+         fn main() { output(fib(input())); }",
+        24,
+    ),
+    (
+        "primes",
+        "fn prime(n) {
+             var d = 2;
+             while d * d <= n {
+                 var m = n;
+                 while m >= d { m = m - d; }
+                 if m == 0 { return 0; }
+                 d = d + 1;
+             }
+             return 1;
+         }
+         fn main() {
+             var n = input();
+             var count = 0;
+             while n > 1 {
+                 count = count + prime(n);
+                 n = n - 1;
+             }
+             output(count);
+         }",
+        3000,
+    ),
+    (
+        "collatz",
+        "fn steps(n) {
+             var count = 0;
+             while n != 1 {
+                 var half = 0;
+                 var rest = n;
+                 while rest > 1 { rest = rest - 2; half = half + 1; }
+                 if rest { n = 3 * n + 1; } else { n = half; }
+                 count = count + 1;
+             }
+             return count;
+         }
+         fn main() {
+             var n = input();
+             var longest = 0;
+             while n > 0 {
+                 var s = steps(n);
+                 if s > longest { longest = s; }
+                 n = n - 1;
+             }
+             output(longest);
+         }",
+        150,
+    ),
+];
+
+fn run(program: &[i64], input: i64) -> i64 {
+    let mut machine = Machine::new(program.to_vec());
+    machine.push_input(input);
+    let mut output = 0;
+    loop {
+        match machine.run().unwrap() {
+            State::Output(x) => output = x,
+            State::Halted => return output,
+            state => panic!("unexpected {:?}", state),
+        }
+    }
+}
+
+fn main() {
+    for (name, source, input) in WORKLOADS {
+        let program = compile(source).unwrap();
+
+        let mut profiler = Profiler::default();
+        let mut machine = Machine::new(program.clone());
+        machine.push_input(input);
+        while !matches!(machine.run_with(&mut profiler).unwrap(), State::Halted) {}
+
+        let start = Instant::now();
+        let output = run(&program, input);
+        let elapsed = start.elapsed();
+        println!(
+            "{:<8} output {:>6}  {:>10} instructions in {:>8.2?} ({:.1}M/s)",
+            name,
+            output,
+            profiler.total(),
+            elapsed,
+            profiler.total() as f64 / elapsed.as_secs_f64() / 1e6
+        );
+    }
+}
@@ -0,0 +1,103 @@
+// Runs the day five diagnostic thousands of times, once giving every machine
+// its own copy of the loaded image and once starting every machine from a
+// shared copy-on-write image, and reports how much each approach allocates.
+// Both start from the same paged image, so only the copying differs.
+// The diagnostic only writes to a few pages, as most searches over a program's
+// inputs do; day two is the exception, since it writes next to nearly every
+// instruction and ends up copying every page.
+//
+//     cargo bench --bench memory
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use rust_advent_2019::intcode::{loader, Machine, Memory, State, PAGE_SIZE};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+struct Report {
+    allocations: usize,
+    bytes: usize,
+    elapsed: Duration,
+    total: i64,
+}
+
+fn measure(f: impl FnOnce() -> i64) -> Report {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = BYTES.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let total = f();
+    Report {
+        elapsed: start.elapsed(),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        bytes: BYTES.load(Ordering::Relaxed) - bytes,
+        total,
+    }
+}
+
+const RUNS: i64 = 5000;
+
+// Sums the diagnostic codes, alternating between the two system IDs.
+fn diagnose(mut start: impl FnMut() -> Machine) -> i64 {
+    let mut total = 0;
+    for run in 0..RUNS {
+        let mut machine = start();
+        machine.push_input(if run % 2 == 0 { 1 } else { 5 });
+        while let Ok(State::Output(code)) = machine.run() {
+            total += code;
+        }
+    }
+    total
+}
+
+// A clone of `image` that shares no pages with it, as if every cell had been
+// copied for the run.
+fn copy(image: &Memory<i64>) -> Memory<i64> {
+    let mut memory = image.clone();
+    for address in (0..memory.len()).step_by(PAGE_SIZE) {
+        memory.set(address, memory[address]);
+    }
+    memory
+}
+
+fn main() {
+    let program: Vec<i64> = loader::load("resources/day_five_input.txt").unwrap();
+    let image = Memory::new(program);
+    let cloned = measure(|| diagnose(|| Machine::from_memory(copy(&image))));
+    let shared = measure(|| diagnose(|| Machine::from_memory(image.clone())));
+    assert_eq!(cloned.total, shared.total);
+
+    println!(
+        "{:>8} {:>12} {:>12} {:>10}",
+        "", "allocations", "bytes", "time"
+    );
+    for (name, report) in [("clone", &cloned), ("cow", &shared)] {
+        println!(
+            "{:>8} {:>12} {:>12} {:>10.2?}",
+            name, report.allocations, report.bytes, report.elapsed
+        );
+    }
+    println!(
+        "copy-on-write allocates {:.1}x fewer bytes",
+        cloned.bytes as f64 / shared.bytes.max(1) as f64
+    );
+}
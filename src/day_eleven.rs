@@ -0,0 +1,179 @@
+// The hull painting robot. The robot's program reads the colour of the panel
+// under it, then outputs the colour to paint it and which way to turn before
+// stepping forward.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::geometry::{self, Direction, Point};
+use crate::intcode::{loader, Error, Machine, State};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Color {
+    Black = 0,
+    White = 1,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Hull {
+    // Panels not in here are black.
+    colors: HashMap<Point, Color>,
+    painted: HashSet<Point>,
+}
+
+impl Hull {
+    pub fn color(&self, panel: Point) -> Color {
+        self.colors.get(&panel).copied().unwrap_or(Color::Black)
+    }
+
+    // Panels painted at least once, whatever colour they ended up.
+    pub fn painted(&self) -> usize {
+        self.painted.len()
+    }
+
+    pub fn render(&self) -> String {
+        let white: Vec<Point> = self
+            .colors
+            .iter()
+            .filter(|(_, color)| **color == Color::White)
+            .map(|(panel, _)| *panel)
+            .collect();
+        geometry::render(&white, |panel| match self.color(panel) {
+            Color::White => '#',
+            Color::Black => ' ',
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PaintError {
+    Machine(Error<i64>),
+    InvalidColor(i64),
+    InvalidTurn(i64),
+    // The program halted between a colour and a turn.
+    Truncated,
+}
+
+impl fmt::Display for PaintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaintError::Machine(e) => write!(f, "robot crashed: {}", e),
+            PaintError::InvalidColor(value) => write!(f, "invalid colour {}", value),
+            PaintError::InvalidTurn(value) => write!(f, "invalid turn {}", value),
+            PaintError::Truncated => write!(f, "robot halted before turning"),
+        }
+    }
+}
+
+impl std::error::Error for PaintError {}
+
+impl From<Error<i64>> for PaintError {
+    fn from(e: Error<i64>) -> Self {
+        PaintError::Machine(e)
+    }
+}
+
+// Runs the robot from the origin, facing up, on a hull that's black apart from
+// the starting panel.
+pub fn paint(program: Vec<i64>, start: Color) -> Result<Hull, PaintError> {
+    let mut machine = Machine::new(program).growable();
+    let mut hull = Hull::default();
+    hull.colors.insert(Point::ORIGIN, start);
+    let (mut position, mut direction) = (Point::ORIGIN, Direction::Up);
+
+    loop {
+        let color = match machine.run()? {
+            State::Output(value) => value,
+            State::NeedsInput => {
+                machine.push_input(hull.color(position) as i64);
+                continue;
+            }
+            State::Halted => return Ok(hull),
+            State::Vetoed => unreachable!(),
+        };
+        let color = match color {
+            0 => Color::Black,
+            1 => Color::White,
+            value => return Err(PaintError::InvalidColor(value)),
+        };
+        direction = match machine.run()? {
+            State::Output(0) => direction.turn_left(),
+            State::Output(1) => direction.turn_right(),
+            State::Output(value) => return Err(PaintError::InvalidTurn(value)),
+            _ => return Err(PaintError::Truncated),
+        };
+
+        hull.colors.insert(position, color);
+        hull.painted.insert(position);
+        position = position.step(direction);
+    }
+}
+
+pub fn solve_first(s: &str) -> usize {
+    paint(loader::parse(s).unwrap(), Color::Black)
+        .unwrap()
+        .painted()
+}
+
+pub fn solve_second(s: &str) -> String {
+    paint(loader::parse(s).unwrap(), Color::White)
+        .unwrap()
+        .render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::lang::compile;
+
+    // The robot from the puzzle's example, which outputs an invalid colour if
+    // it's shown a panel it doesn't expect.
+    fn example(first: i64) -> Vec<i64> {
+        let source = "
+            fn step(expected, color, turn) {
+                if input() != expected { output(9); }
+                output(color);
+                output(turn);
+            }
+            fn main() {
+                step(FIRST, 1, 0);
+                step(0, 0, 0);
+                step(0, 1, 0);
+                step(0, 1, 0);
+                step(1, 0, 1);
+                step(0, 1, 0);
+                step(0, 1, 0);
+            }";
+        compile(&source.replace("FIRST", &first.to_string())).unwrap()
+    }
+
+    #[test]
+    fn paints_the_example() {
+        let hull = paint(example(0), Color::Black).unwrap();
+        assert_eq!(hull.painted(), 6);
+        assert_eq!(hull.color(Point::ORIGIN), Color::Black);
+        assert_eq!(hull.color(Point::new(-1, -1)), Color::White);
+        assert_eq!(hull.render(), "  #\n  #\n## \n");
+    }
+
+    #[test]
+    fn starts_on_a_white_panel() {
+        assert_eq!(
+            paint(example(0), Color::White),
+            Err(PaintError::InvalidColor(9))
+        );
+        assert_eq!(paint(example(1), Color::White).unwrap().painted(), 6);
+    }
+
+    #[test]
+    fn rejects_bad_output() {
+        assert_eq!(
+            paint(vec![104, 1, 104, 2, 99], Color::Black),
+            Err(PaintError::InvalidTurn(2))
+        );
+        assert_eq!(
+            paint(vec![104, 1, 99], Color::Black),
+            Err(PaintError::Truncated)
+        );
+    }
+}
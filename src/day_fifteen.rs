@@ -0,0 +1,233 @@
+// The oxygen system repair droid. The droid's program takes a movement
+// command and reports whether the droid hit a wall, moved, or moved onto the
+// oxygen system. Rather than walking the droid back and forth, the maze is
+// searched breadth first from snapshots of the machine taken at every open
+// position.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::geometry::{self, Direction, Point};
+use crate::intcode::{loader, Error, Machine, State};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Tile {
+    Wall,
+    Open,
+    Oxygen,
+}
+
+fn command(direction: Direction) -> i64 {
+    match direction {
+        Direction::Up => 1,
+        Direction::Down => 2,
+        Direction::Left => 3,
+        Direction::Right => 4,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExploreError {
+    Machine(Error<i64>),
+    InvalidStatus(i64),
+    // The droid halted or asked for another command instead of reporting.
+    Silent,
+}
+
+impl fmt::Display for ExploreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExploreError::Machine(e) => write!(f, "droid crashed: {}", e),
+            ExploreError::InvalidStatus(value) => write!(f, "invalid status {}", value),
+            ExploreError::Silent => write!(f, "droid didn't report after moving"),
+        }
+    }
+}
+
+impl std::error::Error for ExploreError {}
+
+impl From<Error<i64>> for ExploreError {
+    fn from(e: Error<i64>) -> Self {
+        ExploreError::Machine(e)
+    }
+}
+
+// Everything the droid can reach, relative to where it started.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Maze {
+    tiles: HashMap<Point, Tile>,
+}
+
+impl Maze {
+    pub fn tile(&self, point: Point) -> Option<Tile> {
+        self.tiles.get(&point).copied()
+    }
+
+    pub fn oxygen(&self) -> Option<Point> {
+        self.tiles
+            .iter()
+            .find(|(_, tile)| **tile == Tile::Oxygen)
+            .map(|(point, _)| *point)
+    }
+
+    // Steps from `from` to every open position it can reach.
+    pub fn distances(&self, from: Point) -> HashMap<Point, usize> {
+        let mut distances = HashMap::from([(from, 0)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(point) = queue.pop_front() {
+            let distance = distances[&point];
+            for next in point.neighbors() {
+                let open = matches!(self.tile(next), Some(Tile::Open | Tile::Oxygen));
+                if open && !distances.contains_key(&next) {
+                    distances.insert(next, distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+
+    pub fn shortest_path(&self) -> Option<usize> {
+        let oxygen = self.oxygen()?;
+        self.distances(Point::ORIGIN).get(&oxygen).copied()
+    }
+
+    // Minutes for oxygen to spread from the oxygen system to every open
+    // position.
+    pub fn fill_time(&self) -> Option<usize> {
+        let oxygen = self.oxygen()?;
+        self.distances(oxygen).into_values().max()
+    }
+
+    // Walls as `#`, open positions as `.`, the oxygen system as `O` and the
+    // droid's starting position as `D`. Positions never seen are blank.
+    pub fn render(&self) -> String {
+        geometry::render(self.tiles.keys(), |point| match self.tile(point) {
+            _ if point == Point::ORIGIN => 'D',
+            Some(Tile::Wall) => '#',
+            Some(Tile::Open) => '.',
+            Some(Tile::Oxygen) => 'O',
+            None => ' ',
+        })
+    }
+}
+
+fn try_move(droid: &Machine, direction: Direction) -> Result<(Tile, Machine), ExploreError> {
+    let mut droid = droid.clone();
+    droid.push_input(command(direction));
+    let tile = match droid.run()? {
+        State::Output(0) => Tile::Wall,
+        State::Output(1) => Tile::Open,
+        State::Output(2) => Tile::Oxygen,
+        State::Output(value) => return Err(ExploreError::InvalidStatus(value)),
+        _ => return Err(ExploreError::Silent),
+    };
+    Ok((tile, droid))
+}
+
+pub fn explore(program: Vec<i64>) -> Result<Maze, ExploreError> {
+    let mut tiles = HashMap::from([(Point::ORIGIN, Tile::Open)]);
+    let mut queue = VecDeque::from([(Point::ORIGIN, Machine::new(program).growable())]);
+
+    while let Some((point, droid)) = queue.pop_front() {
+        for direction in Direction::ALL {
+            let next = point.step(direction);
+            if tiles.contains_key(&next) {
+                continue;
+            }
+            let (tile, moved) = try_move(&droid, direction)?;
+            tiles.insert(next, tile);
+            if tile != Tile::Wall {
+                queue.push_back((next, moved));
+            }
+        }
+    }
+    Ok(Maze { tiles })
+}
+
+pub fn solve_first(s: &str) -> usize {
+    explore(loader::parse(s).unwrap())
+        .unwrap()
+        .shortest_path()
+        .unwrap()
+}
+
+pub fn solve_second(s: &str) -> usize {
+    explore(loader::parse(s).unwrap())
+        .unwrap()
+        .fill_time()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::lang::compile;
+
+    // The maze from the puzzle's example, with the droid starting in its top
+    // left corner.
+    const DROID: &str = "
+        fn open(x, y) {
+            return (y == 0 && (x == 0 || x == 1))
+                || (y == -1 && (x == 0 || x == 2 || x == 3))
+                || (y == -2 && (x == 0 || x == 1 || x == 2));
+        }
+
+        fn main() {
+            var x = 0;
+            var y = 0;
+            while 1 {
+                var command = input();
+                var nx = x;
+                var ny = y;
+                if command == 1 { ny = y + 1; }
+                if command == 2 { ny = y - 1; }
+                if command == 3 { nx = x - 1; }
+                if command == 4 { nx = x + 1; }
+                if open(nx, ny) {
+                    x = nx;
+                    y = ny;
+                    output(1 + (x == 1 && y == -2));
+                } else {
+                    output(0);
+                }
+            }
+        }";
+
+    fn maze() -> Maze {
+        explore(compile(DROID).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn maps_the_maze() {
+        let maze = maze();
+        assert_eq!(maze.oxygen(), Some(Point::new(1, -2)));
+        assert_eq!(maze.tile(Point::new(1, -1)), Some(Tile::Wall));
+        assert_eq!(maze.tile(Point::new(3, 1)), None);
+        assert_eq!(
+            maze.render(),
+            " ##   \n\
+             #D.## \n\
+             #.#..#\n\
+             #.O.# \n\
+             \x20###  \n"
+        );
+    }
+
+    #[test]
+    fn finds_the_oxygen_system() {
+        let maze = maze();
+        assert_eq!(maze.shortest_path(), Some(3));
+        assert_eq!(maze.fill_time(), Some(4));
+        assert_eq!(maze.distances(Point::ORIGIN)[&Point::new(3, -1)], 6);
+    }
+
+    #[test]
+    fn rejects_bad_status() {
+        assert_eq!(
+            explore(vec![3, 0, 104, 7, 99]),
+            Err(ExploreError::InvalidStatus(7))
+        );
+        assert_eq!(explore(vec![3, 0, 99]), Err(ExploreError::Silent));
+    }
+}
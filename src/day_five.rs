@@ -1,10 +1,18 @@
 #![warn(unused_variables, dead_code)]
 
+use std::fmt;
+
+use crate::intcode::loader::{self, LoadError};
+use crate::intcode::{Cell, Error, Machine, State};
+
+pub const AIR_CONDITIONER: i64 = 1;
+pub const THERMAL_RADIATOR: i64 = 5;
+
 trait GetPlace {
     fn get_place(self, place: Place) -> usize;
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum OpCode {
     Add = 1,
     Mul = 2,
@@ -14,135 +22,209 @@ pub enum OpCode {
     JumpIfFalse = 6,
     LessThan = 7,
     Equals = 8,
+    AdjustRelativeBase = 9,
     Halt = 99,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Mode {
     Position = 0,
     Immediate = 1,
+    Relative = 2,
 }
 
-impl From<usize> for OpCode {
-    fn from(value: usize) -> Self {
+impl TryFrom<usize> for OpCode {
+    type Error = ();
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
         match value % 100 {
-            x if x == OpCode::Add as usize => OpCode::Add,
-            x if x == OpCode::Mul as usize => OpCode::Mul,
-            x if x == OpCode::Input as usize => OpCode::Input,
-            x if x == OpCode::Out as usize => OpCode::Out,
-            x if x == OpCode::JumpIfTrue as usize => OpCode::JumpIfTrue,
-            x if x == OpCode::JumpIfFalse as usize => OpCode::JumpIfFalse,
-            x if x == OpCode::LessThan as usize => OpCode::LessThan,
-            x if x == OpCode::Equals as usize => OpCode::Equals,
-            x if x == OpCode::Halt as usize => OpCode::Halt,
-            _ => panic!("Should have received a valid OpCode, got {}", value),
+            x if x == OpCode::Add as usize => Ok(OpCode::Add),
+            x if x == OpCode::Mul as usize => Ok(OpCode::Mul),
+            x if x == OpCode::Input as usize => Ok(OpCode::Input),
+            x if x == OpCode::Out as usize => Ok(OpCode::Out),
+            x if x == OpCode::JumpIfTrue as usize => Ok(OpCode::JumpIfTrue),
+            x if x == OpCode::JumpIfFalse as usize => Ok(OpCode::JumpIfFalse),
+            x if x == OpCode::LessThan as usize => Ok(OpCode::LessThan),
+            x if x == OpCode::Equals as usize => Ok(OpCode::Equals),
+            x if x == OpCode::AdjustRelativeBase as usize => Ok(OpCode::AdjustRelativeBase),
+            x if x == OpCode::Halt as usize => Ok(OpCode::Halt),
+            _ => Err(()),
         }
     }
 }
 
-impl From<usize> for Mode {
-    fn from(value: usize) -> Self {
+impl TryFrom<usize> for Mode {
+    type Error = ();
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
         match value {
-            x if x == Mode::Immediate as usize => Mode::Immediate,
-            x if x == Mode::Position as usize => Mode::Position,
-            _ => panic!("Should have only received 0 or 1! Got {}", value),
+            x if x == Mode::Immediate as usize => Ok(Mode::Immediate),
+            x if x == Mode::Position as usize => Ok(Mode::Position),
+            x if x == Mode::Relative as usize => Ok(Mode::Relative),
+            _ => Err(()),
         }
     }
 }
 
-fn parse_op(x: usize) -> (Mode, Mode, Mode, OpCode) {
-    (
-        x.get_place(Place::TenThousand).into(),
-        x.get_place(Place::Thousand).into(),
-        x.get_place(Place::Hundred).into(),
-        OpCode::from(x),
-    )
+pub fn decode(x: usize) -> Option<(Mode, Mode, Mode, OpCode)> {
+    Some((
+        x.get_place(Place::TenThousand).try_into().ok()?,
+        x.get_place(Place::Thousand).try_into().ok()?,
+        x.get_place(Place::Hundred).try_into().ok()?,
+        x.try_into().ok()?,
+    ))
 }
 
-fn get_val(ops: &Vec<i64>, index: usize, mode: Mode) -> i64 {
-    match mode {
-        Mode::Position => ops[ops[index] as usize],
-        Mode::Immediate => ops[index],
+impl OpCode {
+    pub fn params(&self) -> usize {
+        match self {
+            OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equals => 3,
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => 2,
+            OpCode::Input | OpCode::Out | OpCode::AdjustRelativeBase => 1,
+            OpCode::Halt => 0,
+        }
+    }
+
+    // 1-based position of the parameter this instruction writes to.
+    pub fn write_param(&self) -> Option<usize> {
+        match self {
+            OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equals => Some(3),
+            OpCode::Input => Some(1),
+            _ => None,
+        }
     }
 }
 
-pub fn perform_ops(ops: &mut Vec<i64>, input: i64) {
-    let mut index = 0;
-    let (mut _mode3, mut mode2, mut mode1, mut op) = parse_op(ops[index] as usize);
+pub fn perform_ops<C: Cell>(ops: &mut [C], input: C) -> Result<(), Error<C>> {
+    for out in run_ops(ops, input)? {
+        println!("Out: {}", out);
+    }
+    Ok(())
+}
 
-    while op != OpCode::Halt {
-        match op {
-            OpCode::Add => {
-                let arg1 = get_val(&ops, index + 1, mode1);
-                let arg2 = get_val(&ops, index + 2, mode2);
-                let target_index = ops[index + 3] as usize;
-                ops[target_index] = arg1 + arg2;
-                index += 4;
-            }
-            OpCode::Mul => {
-                let arg1 = get_val(&ops, index + 1, mode1);
-                let arg2 = get_val(&ops, index + 2, mode2);
-                let target_index = ops[index + 3] as usize;
-                ops[target_index] = arg1 * arg2;
-                index += 4;
-            }
-            OpCode::Input => {
-                let arg = ops[index + 1];
-                ops[arg as usize] = input;
-                index += 2;
-            }
-            OpCode::Out => {
-                let arg = ops[ops[index + 1] as usize];
-                println!("Out: {}", arg);
-                index += 2;
-            }
-            OpCode::JumpIfTrue => {
-                let arg1 = get_val(&ops, index + 1, mode1);
-                let arg2 = get_val(&ops, index + 2, mode2);
-                if arg1 != 0 {
-                    index = arg2 as usize;
-                } else {
-                    index += 3;
-                }
-            }
-            OpCode::JumpIfFalse => {
-                let arg1 = get_val(&ops, index + 1, mode1);
-                let arg2 = get_val(&ops, index + 2, mode2);
-                if arg1 == 0 {
-                    index = arg2 as usize;
-                } else {
-                    index += 3;
-                }
-            }
-            OpCode::LessThan => {
-                let arg1 = get_val(&ops, index + 1, mode1);
-                let arg2 = get_val(&ops, index + 2, mode2);
-                let target_index = ops[index + 3] as usize;
-                if arg1 < arg2 {
-                    ops[target_index] = 1;
-                } else {
-                    ops[target_index] = 0;
-                }
-                index += 4;
-            }
-            OpCode::Equals => {
-                let arg1 = get_val(&ops, index + 1, mode1);
-                let arg2 = get_val(&ops, index + 2, mode2);
-                let target_index = ops[index + 3] as usize;
-                if arg1 == arg2 {
-                    ops[target_index] = 1;
-                } else {
-                    ops[target_index] = 0;
+pub fn run_ops<C: Cell>(ops: &mut [C], input: C) -> Result<Vec<C>, Error<C>> {
+    execute(ops, input, false)
+}
+
+// Like run_ops, but rejects immediate-mode write targets and instructions with
+// digits beyond the modes their opcode uses.
+pub fn run_ops_strict<C: Cell>(ops: &mut [C], input: C) -> Result<Vec<C>, Error<C>> {
+    execute(ops, input, true)
+}
+
+fn execute<C: Cell>(ops: &mut [C], input: C, strict: bool) -> Result<Vec<C>, Error<C>> {
+    let mut machine = Machine::new(ops.to_vec());
+    if strict {
+        machine = machine.strict();
+    }
+
+    let mut outputs = vec![];
+    let result = loop {
+        match machine.run() {
+            Ok(State::Output(x)) => outputs.push(x),
+            Ok(State::NeedsInput) => machine.push_input(input.clone()),
+            Ok(State::Halted) => break Ok(outputs),
+            Ok(State::Vetoed) => unreachable!(),
+            Err(e) => break Err(e),
+        }
+    };
+
+    for (op, value) in ops.iter_mut().zip(machine.memory().iter()) {
+        *op = value.clone();
+    }
+    result
+}
+
+// A test output that wasn't zero. `test` counts outputs from 0, and `ip` is
+// where the output instruction was.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TestFailure<C> {
+    pub test: usize,
+    pub ip: usize,
+    pub value: C,
+}
+
+#[derive(Debug)]
+pub enum DiagnosticError<C> {
+    Load(LoadError),
+    Machine(Error<C>),
+    NoDiagnosticCode,
+    Failed(Vec<TestFailure<C>>),
+}
+
+impl<C: fmt::Display> fmt::Display for DiagnosticError<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiagnosticError::Load(e) => write!(f, "{}", e),
+            DiagnosticError::Machine(e) => write!(f, "diagnostic crashed: {}", e),
+            DiagnosticError::NoDiagnosticCode => write!(f, "no diagnostic code"),
+            DiagnosticError::Failed(failures) => {
+                write!(f, "{} tests failed:", failures.len())?;
+                for failure in failures {
+                    write!(
+                        f,
+                        "\n  test {} at ip {} output {}",
+                        failure.test, failure.ip, failure.value
+                    )?;
                 }
-                index += 4;
+                Ok(())
             }
-            _ => panic!("Invalid opcode {:?} at index {:?}", ops[index], index),
         }
+    }
+}
+
+impl<C: fmt::Debug + fmt::Display> std::error::Error for DiagnosticError<C> {}
 
-        (_mode3, mode2, mode1, op) = parse_op(ops[index] as usize);
+impl<C> From<LoadError> for DiagnosticError<C> {
+    fn from(e: LoadError) -> Self {
+        DiagnosticError::Load(e)
     }
 }
 
+impl<C> From<Error<C>> for DiagnosticError<C> {
+    fn from(e: Error<C>) -> Self {
+        DiagnosticError::Machine(e)
+    }
+}
+
+// Runs the diagnostic program for a system. Every output but the last is a
+// test that passed if it's zero, and the last is the diagnostic code.
+pub fn diagnose<C: Cell>(ops: Vec<C>, system: C) -> Result<C, DiagnosticError<C>> {
+    let mut machine = Machine::new(ops);
+    let mut outputs = vec![];
+    loop {
+        match machine.run()? {
+            // The output instruction and its parameter are behind the ip.
+            State::Output(x) => outputs.push((machine.ip() - 2, x)),
+            State::NeedsInput => machine.push_input(system.clone()),
+            State::Halted => break,
+            State::Vetoed => unreachable!(),
+        }
+    }
+
+    let (_, code) = outputs.pop().ok_or(DiagnosticError::NoDiagnosticCode)?;
+    let failures: Vec<TestFailure<C>> = outputs
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, value))| !value.is_zero())
+        .map(|(test, (ip, value))| TestFailure { test, ip, value })
+        .collect();
+    match failures.is_empty() {
+        true => Ok(code),
+        false => Err(DiagnosticError::Failed(failures)),
+    }
+}
+
+pub fn solve_first(s: &str) -> Result<i64, DiagnosticError<i64>> {
+    let ops: Vec<i64> = loader::parse(s)?;
+    diagnose(ops, AIR_CONDITIONER)
+}
+
+pub fn solve_second(s: &str) -> Result<i64, DiagnosticError<i64>> {
+    let ops: Vec<i64> = loader::parse(s)?;
+    diagnose(ops, THERMAL_RADIATOR)
+}
+
 pub enum Place {
     Hundred,
     Thousand,
@@ -162,6 +244,7 @@ impl GetPlace for usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::intcode::BigInt;
 
     #[test]
     fn get_hundred_place() {
@@ -188,14 +271,83 @@ mod tests {
     }
 
     #[test]
-    fn scratch() {
-        let path = std::path::Path::new("resources/day_five_input.txt");
-        let mut input: Vec<i64> = std::fs::read_to_string(path)
-            .unwrap()
-            .split(",")
-            .map(|x| x.parse().unwrap())
+    fn solves_with_the_input() {
+        let input = std::fs::read_to_string("resources/day_five_input.txt").unwrap();
+        assert_eq!(solve_first(&input).unwrap(), 16209841);
+        assert_eq!(solve_second(&input).unwrap(), 8834787);
+        assert!(matches!(
+            solve_first("3,0,x,99"),
+            Err(DiagnosticError::Load(LoadError::InvalidToken {
+                offset: 4,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn reports_failed_tests() {
+        let ops: Vec<i64> = vec![104, 0, 104, 7, 3, 13, 104, 0, 4, 13, 104, 42, 99, 0];
+        let Err(DiagnosticError::Failed(failures)) = diagnose(ops, 5) else {
+            panic!("expected failed tests");
+        };
+        assert_eq!(
+            failures,
+            vec![
+                TestFailure {
+                    test: 1,
+                    ip: 2,
+                    value: 7
+                },
+                TestFailure {
+                    test: 3,
+                    ip: 8,
+                    value: 5
+                },
+            ]
+        );
+        assert!(matches!(
+            diagnose(vec![99i64], 1),
+            Err(DiagnosticError::NoDiagnosticCode)
+        ));
+        assert_eq!(diagnose(vec![104i64, 0, 104, 0, 104, 9, 99], 1).unwrap(), 9);
+    }
+
+    #[test]
+    fn reports_overflow_with_ip() {
+        let mut ops: Vec<i64> = vec![1101, 1, i64::MAX - 1, 9, 1002, 9, 3, 9, 99, 0];
+        assert_eq!(perform_ops(&mut ops, 0), Err(Error::Overflow { ip: 4 }));
+    }
+
+    #[test]
+    fn runs_with_wider_cells() {
+        let big = i128::from(i64::MAX);
+        let mut ops: Vec<i128> = vec![1002, 5, 3, 5, 99, big];
+        perform_ops(&mut ops, 0).unwrap();
+        assert_eq!(ops[5], big * 3);
+
+        let mut ops: Vec<BigInt> = [1102, 0, 0, 0, 99]
+            .iter()
+            .map(|x| BigInt::from(*x))
             .collect();
+        ops[1] = BigInt::from(i64::MAX);
+        ops[2] = BigInt::from(i64::MAX);
+        perform_ops(&mut ops, BigInt::from(0)).unwrap();
+        assert_eq!(ops[0].to_string(), "85070591730234615847396907784232501249");
+    }
 
-        perform_ops(&mut input, 1);
+    #[test]
+    fn uses_relative_base() {
+        let mut ops: Vec<i64> = vec![109, 9, 21101, 2, 3, 0, 204, 0, 99, 0];
+        assert_eq!(run_ops(&mut ops, 0), Ok(vec![5]));
+        assert_eq!(ops[9], 5);
+    }
+
+    #[test]
+    fn rejects_negative_addresses() {
+        let mut ops: Vec<i64> = vec![1, -1, 0, 0, 99];
+        assert_eq!(
+            perform_ops(&mut ops, 0),
+            Err(Error::InvalidAddress { ip: 0, address: -1 })
+        );
     }
 }
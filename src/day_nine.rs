@@ -0,0 +1,198 @@
+// The BOOST program. In test mode it checks the interpreter's opcodes and
+// parameter modes, and either outputs a single keycode or the instructions it
+// found malfunctioning, which makes any puzzle input a regression test for
+// the machine.
+
+use std::fmt;
+
+use crate::day_five::{decode, Mode, OpCode};
+use crate::intcode::{loader, Error, Machine, State};
+
+pub const TEST_MODE: i64 = 1;
+pub const SENSOR_MODE: i64 = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum BoostError {
+    Machine(Error<i64>),
+    // BOOST asked for more input than the mode.
+    Waiting,
+}
+
+impl fmt::Display for BoostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoostError::Machine(e) => write!(f, "BOOST crashed: {}", e),
+            BoostError::Waiting => write!(f, "BOOST is still waiting for input"),
+        }
+    }
+}
+
+impl std::error::Error for BoostError {}
+
+impl From<Error<i64>> for BoostError {
+    fn from(e: Error<i64>) -> Self {
+        BoostError::Machine(e)
+    }
+}
+
+pub fn boost(program: Vec<i64>, mode: i64) -> Result<Vec<i64>, BoostError> {
+    let mut machine = Machine::new(program).growable();
+    machine.push_input(mode);
+    let mut outputs = vec![];
+    loop {
+        match machine.run()? {
+            State::Output(value) => outputs.push(value),
+            State::Halted => return Ok(outputs),
+            State::NeedsInput => return Err(BoostError::Waiting),
+            State::Vetoed => unreachable!(),
+        }
+    }
+}
+
+// An instruction BOOST reported as malfunctioning, decoded if it's one the
+// machine knows. Only the modes of the opcode's own parameters are kept.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Malfunction {
+    pub value: i64,
+    pub instruction: Option<(OpCode, Vec<Mode>)>,
+}
+
+impl Malfunction {
+    pub fn new(value: i64) -> Self {
+        let decoded = usize::try_from(value).ok().and_then(decode);
+        let instruction = decoded.map(|(third, second, first, op)| {
+            let modes = [first, second, third].into_iter().take(op.params());
+            (op, modes.collect())
+        });
+        Malfunction { value, instruction }
+    }
+}
+
+impl fmt::Display for Malfunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some((op, modes)) = &self.instruction else {
+            return write!(f, "{}: unknown instruction", self.value);
+        };
+        let modes: Vec<String> = modes.iter().map(|m| format!("{:?}", m)).collect();
+        write!(f, "{}: {:?}({})", self.value, op, modes.join(", "))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SelfTest {
+    Passed {
+        keycode: i64,
+    },
+    // BOOST stopped without any output at all.
+    NoKeycode,
+    // Every output before the final keycode, when there are any.
+    Failed {
+        malfunctions: Vec<Malfunction>,
+        keycode: i64,
+    },
+}
+
+impl fmt::Display for SelfTest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelfTest::Passed { keycode } => write!(f, "BOOST passed, keycode {}", keycode),
+            SelfTest::NoKeycode => write!(f, "BOOST stopped without a keycode"),
+            SelfTest::Failed {
+                malfunctions,
+                keycode,
+            } => {
+                write!(
+                    f,
+                    "BOOST found {} malfunctions before keycode {}:",
+                    malfunctions.len(),
+                    keycode
+                )?;
+                for malfunction in malfunctions {
+                    write!(f, "\n  {}", malfunction)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+pub fn self_test(program: Vec<i64>) -> Result<SelfTest, BoostError> {
+    let outputs = boost(program, TEST_MODE)?;
+    Ok(match outputs.split_last() {
+        None => SelfTest::NoKeycode,
+        Some((&keycode, [])) => SelfTest::Passed { keycode },
+        Some((&keycode, malfunctions)) => SelfTest::Failed {
+            malfunctions: malfunctions.iter().copied().map(Malfunction::new).collect(),
+            keycode,
+        },
+    })
+}
+
+pub fn solve_first(s: &str) -> i64 {
+    match self_test(loader::parse(s).unwrap()).unwrap() {
+        SelfTest::Passed { keycode } => keycode,
+        failed => panic!("{}", failed),
+    }
+}
+
+pub fn solve_second(s: &str) -> i64 {
+    let outputs = boost(loader::parse(s).unwrap(), SENSOR_MODE).unwrap();
+    assert_eq!(outputs.len(), 1, "{:?}", outputs);
+    outputs[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUINE: [i64; 16] = [
+        109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+    ];
+
+    #[test]
+    fn passes_with_a_keycode() {
+        assert_eq!(
+            self_test(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0]),
+            Ok(SelfTest::Passed {
+                keycode: 1219070632396864
+            })
+        );
+        assert_eq!(solve_first("104,1125899906842624,99"), 1125899906842624);
+        assert_eq!(self_test(vec![99]), Ok(SelfTest::NoKeycode));
+        assert_eq!(self_test(vec![3, 0, 3, 0, 99]), Err(BoostError::Waiting));
+        assert_eq!(
+            self_test(QUINE.to_vec()).unwrap(),
+            SelfTest::Failed {
+                malfunctions: QUINE[..15].iter().map(|&v| Malfunction::new(v)).collect(),
+                keycode: 99
+            }
+        );
+    }
+
+    #[test]
+    fn decodes_malfunctions() {
+        let report = self_test(vec![104, 203, 104, 21107, 104, 0, 104, -4, 99]).unwrap();
+        let SelfTest::Failed { malfunctions, .. } = &report else {
+            panic!("{}", report);
+        };
+        assert_eq!(
+            malfunctions[0].instruction,
+            Some((OpCode::Input, vec![Mode::Relative]))
+        );
+        assert_eq!(
+            malfunctions[1].instruction,
+            Some((
+                OpCode::LessThan,
+                vec![Mode::Immediate, Mode::Immediate, Mode::Relative]
+            ))
+        );
+        assert_eq!(malfunctions[2].instruction, None);
+        assert_eq!(
+            report.to_string(),
+            "BOOST found 3 malfunctions before keycode -4:\n  \
+             203: Input(Relative)\n  \
+             21107: LessThan(Immediate, Immediate, Relative)\n  \
+             0: unknown instruction"
+        );
+    }
+}
@@ -0,0 +1,200 @@
+// The tractor beam. The drone program takes a position and outputs whether the
+// beam pulls there, then halts, so every query runs a fresh machine. Machines
+// start from one shared copy-on-write image, and `runs` counts how many there
+// have been.
+
+use std::fmt;
+
+use crate::geometry::Point;
+use crate::intcode::{loader, Error, Machine, Memory, State};
+
+// How far down `fit` looks before giving up.
+pub const SEARCH_LIMIT: i64 = 100_000;
+
+#[derive(Debug, PartialEq)]
+pub enum BeamError {
+    Machine(Error<i64>),
+    InvalidOutput(i64),
+    // The drone halted or asked for more input without reporting.
+    Silent,
+}
+
+impl fmt::Display for BeamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BeamError::Machine(e) => write!(f, "drone crashed: {}", e),
+            BeamError::InvalidOutput(value) => write!(f, "invalid drone report {}", value),
+            BeamError::Silent => write!(f, "drone didn't report"),
+        }
+    }
+}
+
+impl std::error::Error for BeamError {}
+
+impl From<Error<i64>> for BeamError {
+    fn from(e: Error<i64>) -> Self {
+        BeamError::Machine(e)
+    }
+}
+
+pub struct Beam {
+    image: Memory<i64>,
+    runs: usize,
+}
+
+impl Beam {
+    pub fn new(program: Vec<i64>) -> Self {
+        Beam {
+            image: Memory::new(program),
+            runs: 0,
+        }
+    }
+
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    pub fn pulls(&mut self, x: i64, y: i64) -> Result<bool, BeamError> {
+        self.runs += 1;
+        let mut drone = Machine::from_memory(self.image.clone()).growable();
+        drone.push_input(x);
+        drone.push_input(y);
+        match drone.run()? {
+            State::Output(0) => Ok(false),
+            State::Output(1) => Ok(true),
+            State::Output(value) => Err(BeamError::InvalidOutput(value)),
+            _ => Err(BeamError::Silent),
+        }
+    }
+
+    // Positions pulled in the `size` by `size` square from the emitter.
+    pub fn scan(&mut self, size: i64) -> Result<usize, BeamError> {
+        let mut count = 0;
+        for y in 0..size {
+            for x in 0..size {
+                count += self.pulls(x, y)? as usize;
+            }
+        }
+        Ok(count)
+    }
+
+    // The top left corner of the closest `size` by `size` square inside the
+    // beam. Follows the beam's left edge down one row at a time, and checks
+    // whether the square whose bottom left corner is on the edge has its top
+    // right corner in the beam too, which for a beam without holes means the
+    // whole square fits. The left edge is assumed to move right by at most
+    // `y` columns on row `y`, and rows where it isn't found within that are
+    // skipped, as happens near the emitter.
+    pub fn fit(&mut self, size: i64) -> Result<Option<Point>, BeamError> {
+        let mut left = 0;
+        for y in size - 1..SEARCH_LIMIT {
+            let mut edge = None;
+            for x in left..=left + y {
+                if self.pulls(x, y)? {
+                    edge = Some(x);
+                    break;
+                }
+            }
+            let Some(x) = edge else {
+                continue;
+            };
+            left = x;
+            if self.pulls(x + size - 1, y - size + 1)? {
+                return Ok(Some(Point::new(x, y - size + 1)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+pub fn solve_first(s: &str) -> usize {
+    Beam::new(loader::parse(s).unwrap()).scan(50).unwrap()
+}
+
+pub fn solve_second(s: &str) -> i64 {
+    let corner = Beam::new(loader::parse(s).unwrap())
+        .fit(100)
+        .unwrap()
+        .unwrap();
+    corner.x * 10000 + corner.y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::lang::compile;
+
+    // A beam between x = 0.7y and x = 1.6y.
+    fn pulls(x: i64, y: i64) -> bool {
+        7 * y <= 10 * x && 5 * x <= 8 * y
+    }
+
+    const DRONE: &str = "
+        fn main() {
+            var x = input();
+            var y = input();
+            output(7 * y <= 10 * x && 5 * x <= 8 * y);
+        }";
+
+    fn beam() -> Beam {
+        Beam::new(compile(DRONE).unwrap())
+    }
+
+    #[test]
+    fn scans_the_area() {
+        let mut beam = beam();
+        let expected = (0..50)
+            .flat_map(|y| (0..50).map(move |x| (x, y)))
+            .filter(|&(x, y)| pulls(x, y))
+            .count();
+        assert_eq!(beam.scan(50), Ok(expected));
+        assert_eq!(beam.runs(), 2500);
+    }
+
+    #[test]
+    fn fits_squares_along_the_edge() {
+        for size in [1, 2, 10, 30] {
+            let closest = (0..)
+                .flat_map(|y| (0..=2 * y).map(move |x| (x, y)))
+                .find(|&(x, y)| pulls(x + size - 1, y) && pulls(x, y + size - 1))
+                .unwrap();
+
+            let mut beam = beam();
+            let corner = beam.fit(size).unwrap().unwrap();
+            assert_eq!((corner.x, corner.y), closest, "size {}", size);
+            if size >= 10 {
+                let area = (corner.y + size) * (corner.x + size);
+                assert!((beam.runs() as i64) < area / 4, "{} runs", beam.runs());
+            }
+        }
+    }
+
+    #[test]
+    fn skips_empty_rows_near_the_emitter() {
+        // Between x = 0.5y and x = 0.55y, which misses every odd row before
+        // y = 11.
+        let source = "fn main() {
+            var x = input();
+            var y = input();
+            output(2 * x >= y && 20 * x <= 11 * y);
+        }";
+        let mut beam = Beam::new(compile(source).unwrap());
+        assert_eq!(beam.scan(10), Ok(5));
+        let narrow = |x: i64, y: i64| 2 * x >= y && 20 * x <= 11 * y;
+        let closest = (0..)
+            .flat_map(|y| (0..=y).map(move |x| (x, y)))
+            .find(|&(x, y)| narrow(x + 2, y) && narrow(x, y + 2))
+            .unwrap();
+        let corner = beam.fit(3).unwrap().unwrap();
+        assert_eq!((corner.x, corner.y), closest);
+    }
+
+    #[test]
+    fn rejects_bad_reports() {
+        assert_eq!(
+            Beam::new(vec![104, 2, 99]).pulls(0, 0),
+            Err(BeamError::InvalidOutput(2))
+        );
+        assert_eq!(Beam::new(vec![99]).pulls(0, 0), Err(BeamError::Silent));
+    }
+}
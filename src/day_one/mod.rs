@@ -1,47 +1,164 @@
-use std::fs;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
 
-fn num_to_fuel(n: i64) -> i64 {
-    (n / 3) - 2
+pub mod report;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MassError {
+    // Lines count from 1.
+    Invalid { line: usize, text: String },
+    Negative { line: usize, mass: i64 },
 }
 
-fn str_to_fuel(n: &str) -> i64 {
-    num_to_fuel(n.parse::<i64>().unwrap())
+impl fmt::Display for MassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MassError::Invalid { line, text } => {
+                write!(f, "line {}: invalid mass {:?}", line, text)
+            }
+            MassError::Negative { line, mass } => {
+                write!(f, "line {}: negative mass {}", line, mass)
+            }
+        }
+    }
 }
 
-pub fn solve_first(path: &std::path::Path) -> i64 {
-    let contents = fs::read_to_string(path).expect(format!("Missing file {:?}", path).as_str());
-    let nums: Vec<i64> = contents
-        .split("\n")
-        .into_iter()
-        .map(|x| str_to_fuel(x))
-        .collect();
+impl std::error::Error for MassError {}
 
-    let mut acc = 0;
-    for num in nums {
-        acc += num;
+fn parse_mass(line: usize, text: &str) -> Result<Option<i64>, MassError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let mass: i64 = text.parse().map_err(|_| MassError::Invalid {
+        line,
+        text: text.to_string(),
+    })?;
+    if mass < 0 {
+        return Err(MassError::Negative { line, mass });
     }
-    acc
+    Ok(Some(mass))
 }
 
-fn str_to_all_fuel(n: &str) -> i64 {
-    let mut current_fuel = str_to_fuel(n);
-    let mut total_fuel = 0;
-    while current_fuel > 0 {
-        total_fuel += current_fuel;
-        current_fuel = num_to_fuel(current_fuel);
+// One mass per line. Blank lines are skipped.
+pub fn parse_masses(s: &str) -> Result<Vec<i64>, MassError> {
+    let mut masses = vec![];
+    for (i, text) in s.lines().enumerate() {
+        masses.extend(parse_mass(i + 1, text)?);
     }
-    total_fuel
+    Ok(masses)
 }
 
-pub fn solve_second(path: &std::path::Path) -> i64 {
-    let contents = fs::read_to_string(path).expect(format!("Missing file {:?}", path).as_str());
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Mass(MassError),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "couldn't read masses: {}", e),
+            ReadError::Mass(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+impl From<MassError> for ReadError {
+    fn from(e: MassError) -> Self {
+        ReadError::Mass(e)
+    }
+}
+
+// Masses read a line at a time into one buffer, so the whole input is never
+// in memory.
+pub struct Masses<R> {
+    reader: R,
+    line: usize,
+    buffer: String,
+}
+
+pub fn masses<R: BufRead>(reader: R) -> Masses<R> {
+    Masses {
+        reader,
+        line: 0,
+        buffer: String::new(),
+    }
+}
+
+impl<R: BufRead> Iterator for Masses<R> {
+    type Item = Result<i64, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                // The line was consumed even though it couldn't be read.
+                Err(e) => {
+                    self.line += 1;
+                    return Some(Err(e.into()));
+                }
+            }
+            match parse_mass(self.line, &self.buffer) {
+                Ok(None) => continue,
+                Ok(Some(mass)) => return Some(Ok(mass)),
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+fn num_to_fuel(n: i64) -> i64 {
+    (n / 3) - 2
+}
+
+// Fuel for the mass, then for that fuel, and so on until it needs none.
+fn fuel_chain(n: i64) -> impl Iterator<Item = i64> {
+    std::iter::successors(Some(num_to_fuel(n)), |&fuel| Some(num_to_fuel(fuel)))
+        .take_while(|&fuel| fuel > 0)
+}
+
+fn num_to_all_fuel(n: i64) -> i64 {
+    fuel_chain(n).sum()
+}
+
+pub fn fuel_for(masses: impl IntoIterator<Item = i64>) -> i64 {
+    masses.into_iter().map(num_to_fuel).sum()
+}
+
+pub fn all_fuel_for(masses: impl IntoIterator<Item = i64>) -> i64 {
+    masses.into_iter().map(num_to_all_fuel).sum()
+}
+
+pub fn read_fuel(reader: impl BufRead) -> Result<i64, ReadError> {
+    masses(reader).try_fold(0, |total, mass| Ok(total + num_to_fuel(mass?)))
+}
+
+pub fn read_all_fuel(reader: impl BufRead) -> Result<i64, ReadError> {
+    masses(reader).try_fold(0, |total, mass| Ok(total + num_to_all_fuel(mass?)))
+}
+
+fn open(path: &std::path::Path) -> BufReader<File> {
+    BufReader::new(File::open(path).unwrap_or_else(|_| panic!("Missing file {:?}", path)))
+}
+
+pub fn solve_first(path: &std::path::Path) -> i64 {
+    read_fuel(open(path)).unwrap_or_else(|e| panic!("{:?}: {}", path, e))
+}
 
-    contents
-        .split("\n")
-        .into_iter()
-        .map(|x| str_to_all_fuel(x))
-        .reduce(|acc, x| acc + x)
-        .unwrap()
+pub fn solve_second(path: &std::path::Path) -> i64 {
+    read_all_fuel(open(path)).unwrap_or_else(|e| panic!("{:?}: {}", path, e))
 }
 
 #[cfg(test)]
@@ -74,4 +191,73 @@ mod tests {
         let num = solve_second(std::path::Path::new(path));
         assert_eq!(5148724, num);
     }
+
+    #[test]
+    fn skips_blank_lines() {
+        assert_eq!(
+            parse_masses("12\n\n 14 \r\n1969\n\n"),
+            Ok(vec![12, 14, 1969])
+        );
+        assert_eq!(parse_masses(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn rejects_bad_masses() {
+        assert_eq!(
+            parse_masses("12\n\n1x4\n"),
+            Err(MassError::Invalid {
+                line: 3,
+                text: "1x4".to_string()
+            })
+        );
+        assert_eq!(
+            parse_masses("12\n-14\n"),
+            Err(MassError::Negative { line: 2, mass: -14 })
+        );
+        assert_eq!(
+            MassError::Invalid {
+                line: 3,
+                text: "1x4".to_string()
+            }
+            .to_string(),
+            "line 3: invalid mass \"1x4\""
+        );
+    }
+
+    #[test]
+    fn streams_from_readers() {
+        let manifest = "12\n14\n\n1969\n100756";
+        assert_eq!(read_fuel(manifest.as_bytes()).unwrap(), 34241);
+        assert_eq!(read_all_fuel(manifest.as_bytes()).unwrap(), 51316);
+
+        let masses = [12, 14, 1969, 100756];
+        assert_eq!(fuel_for(masses), 34241);
+        assert_eq!(all_fuel_for(masses.iter().copied()), 51316);
+    }
+
+    #[test]
+    fn reports_read_errors() {
+        let mut read = masses("12\n\n-3\n14\n".as_bytes());
+        assert_eq!(read.next().unwrap().unwrap(), 12);
+        assert!(matches!(
+            read.next(),
+            Some(Err(ReadError::Mass(MassError::Negative {
+                line: 3,
+                mass: -3
+            })))
+        ));
+        assert_eq!(read.next().unwrap().unwrap(), 14);
+        assert!(read.next().is_none());
+
+        let error = read_fuel(&[b'1', b'\n', 0xff, b'\n'][..]).unwrap_err();
+        assert!(matches!(error, ReadError::Io(_)), "{}", error);
+
+        let mut read = masses(&[b'1', b'\n', 0xff, b'\n', b'-', b'3', b'\n'][..]);
+        assert_eq!(read.next().unwrap().unwrap(), 1);
+        assert!(matches!(read.next(), Some(Err(ReadError::Io(_)))));
+        assert!(matches!(
+            read.next(),
+            Some(Err(ReadError::Mass(MassError::Negative { line: 3, .. })))
+        ));
+    }
 }
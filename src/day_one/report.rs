@@ -0,0 +1,154 @@
+// Each module's fuel, for checking the numbers behind the totals.
+
+use std::io::BufRead;
+
+use super::{fuel_chain, masses, num_to_all_fuel, num_to_fuel, ReadError};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ModuleFuel {
+    pub mass: i64,
+    pub direct: i64,
+    pub recursive: i64,
+    // The fuel added at each step of the recursive calculation.
+    pub chain: Vec<i64>,
+}
+
+impl ModuleFuel {
+    pub fn new(mass: i64) -> Self {
+        ModuleFuel {
+            mass,
+            direct: num_to_fuel(mass),
+            recursive: num_to_all_fuel(mass),
+            chain: fuel_chain(mass).collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Report {
+    pub modules: Vec<ModuleFuel>,
+    pub mass: i64,
+    pub direct: i64,
+    pub recursive: i64,
+}
+
+impl Report {
+    pub fn new(masses: impl IntoIterator<Item = i64>) -> Self {
+        let mut report = Report::default();
+        for mass in masses {
+            report.push(ModuleFuel::new(mass));
+        }
+        report
+    }
+
+    pub fn read(reader: impl BufRead) -> Result<Self, ReadError> {
+        let mut report = Report::default();
+        for mass in masses(reader) {
+            report.push(ModuleFuel::new(mass?));
+        }
+        Ok(report)
+    }
+
+    fn push(&mut self, module: ModuleFuel) {
+        self.mass += module.mass;
+        self.direct += module.direct;
+        self.recursive += module.recursive;
+        self.modules.push(module);
+    }
+
+    // A row per module numbered from 1, then the totals. The chain is
+    // separated by spaces.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("module,mass,direct_fuel,recursive_fuel,chain\n");
+        for (i, module) in self.modules.iter().enumerate() {
+            let chain: Vec<String> = module.chain.iter().map(i64::to_string).collect();
+            csv += &format!(
+                "{},{},{},{},{}\n",
+                i + 1,
+                module.mass,
+                module.direct,
+                module.recursive,
+                chain.join(" ")
+            );
+        }
+        csv += &format!("total,{},{},{},\n", self.mass, self.direct, self.recursive);
+        csv
+    }
+
+    // A module per line, so reviewers can diff reports.
+    pub fn to_json(&self) -> String {
+        let modules: Vec<String> = self
+            .modules
+            .iter()
+            .map(|module| {
+                let chain: Vec<String> = module.chain.iter().map(i64::to_string).collect();
+                format!(
+                    "    {{\"mass\": {}, \"direct_fuel\": {}, \"recursive_fuel\": {}, \"chain\": [{}]}}",
+                    module.mass,
+                    module.direct,
+                    module.recursive,
+                    chain.join(", ")
+                )
+            })
+            .collect();
+        let modules = match modules.is_empty() {
+            true => "[]".to_string(),
+            false => format!("[\n{}\n  ]", modules.join(",\n")),
+        };
+        format!(
+            "{{\n  \"modules\": {},\n  \"totals\": {{\"mass\": {}, \"direct_fuel\": {}, \"recursive_fuel\": {}}}\n}}\n",
+            modules, self.mass, self.direct, self.recursive
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::BufReader;
+
+    #[test]
+    fn breaks_down_each_module() {
+        let report = Report::new([14, 1969]);
+        assert_eq!(
+            report.modules[1],
+            ModuleFuel {
+                mass: 1969,
+                direct: 654,
+                recursive: 966,
+                chain: vec![654, 216, 70, 21, 5],
+            }
+        );
+        assert_eq!(
+            (report.mass, report.direct, report.recursive),
+            (1983, 656, 968)
+        );
+        assert_eq!(
+            report.to_csv(),
+            "module,mass,direct_fuel,recursive_fuel,chain\n\
+             1,14,2,2,2\n\
+             2,1969,654,966,654 216 70 21 5\n\
+             total,1983,656,968,\n"
+        );
+        assert_eq!(
+            report.to_json(),
+            "{\n  \"modules\": [\n    \
+             {\"mass\": 14, \"direct_fuel\": 2, \"recursive_fuel\": 2, \"chain\": [2]},\n    \
+             {\"mass\": 1969, \"direct_fuel\": 654, \"recursive_fuel\": 966, \"chain\": [654, 216, 70, 21, 5]}\n  \
+             ],\n  \"totals\": {\"mass\": 1983, \"direct_fuel\": 656, \"recursive_fuel\": 968}\n}\n"
+        );
+        assert_eq!(
+            Report::new([]).to_json(),
+            "{\n  \"modules\": [],\n  \"totals\": {\"mass\": 0, \"direct_fuel\": 0, \"recursive_fuel\": 0}\n}\n"
+        );
+    }
+
+    #[test]
+    fn totals_the_input() {
+        let input = File::open("resources/day_one_input.txt").unwrap();
+        let report = Report::read(BufReader::new(input)).unwrap();
+        assert_eq!(report.modules.len(), 100);
+        assert_eq!((report.direct, report.recursive), (3434390, 5148724));
+    }
+}
@@ -0,0 +1,428 @@
+// The scaffold vacuum robot. Asleep, its program prints what the cameras see
+// and halts. Woken up by setting address 0 to 2, it takes a movement routine
+// as four lines of text, a main routine calling functions A, B and C and the
+// functions themselves, each at most 20 characters, then reports the dust it
+// collected as a value outside ASCII.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::geometry::{Direction, Point};
+use crate::intcode::ascii::Ascii;
+use crate::intcode::{loader, Error, Machine};
+
+pub const MAX_ROUTINE: usize = 20;
+pub const FUNCTIONS: [char; 3] = ['A', 'B', 'C'];
+
+#[derive(Debug, PartialEq)]
+pub enum ScaffoldError {
+    Machine(Error<i64>),
+    // Rows count down from the top of the image.
+    InvalidPixel { x: usize, row: usize, pixel: char },
+    NoRobot,
+    // The robot is off the scaffold, shown as `X`.
+    Tumbling,
+    Uncompressible,
+    // The program is empty, so there's no address 0 to wake the robot with.
+    Asleep,
+    // The robot halted or asked for more input without reporting any dust.
+    NoDust(String),
+}
+
+impl fmt::Display for ScaffoldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScaffoldError::Machine(e) => write!(f, "robot crashed: {}", e),
+            ScaffoldError::InvalidPixel { x, row, pixel } => {
+                write!(f, "invalid pixel {:?} at {},{}", pixel, x, row)
+            }
+            ScaffoldError::NoRobot => write!(f, "no robot in the image"),
+            ScaffoldError::Tumbling => write!(f, "robot is tumbling through space"),
+            ScaffoldError::Uncompressible => write!(f, "path doesn't fit in three functions"),
+            ScaffoldError::Asleep => write!(f, "empty program can't be woken up"),
+            ScaffoldError::NoDust(text) => write!(f, "robot didn't report dust: {:?}", text),
+        }
+    }
+}
+
+impl std::error::Error for ScaffoldError {}
+
+impl From<Error<i64>> for ScaffoldError {
+    fn from(e: Error<i64>) -> Self {
+        ScaffoldError::Machine(e)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Step {
+    Left,
+    Right,
+    Forward(usize),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Left => write!(f, "L"),
+            Step::Right => write!(f, "R"),
+            Step::Forward(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+// Steps as the robot reads them, like `R,8,L,4`.
+pub fn routine(steps: &[Step]) -> String {
+    let steps: Vec<String> = steps.iter().map(Step::to_string).collect();
+    steps.join(",")
+}
+
+// What the cameras see. Row `r` of the image is at `y = -r`, so that up in
+// the image is `Direction::Up`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Camera {
+    scaffold: HashSet<Point>,
+    robot: Point,
+    facing: Direction,
+}
+
+impl FromStr for Camera {
+    type Err = ScaffoldError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut scaffold = HashSet::new();
+        let mut robot = None;
+        for (row, line) in s.lines().enumerate() {
+            for (x, pixel) in line.chars().enumerate() {
+                let point = Point::new(x as i64, -(row as i64));
+                let facing = match pixel {
+                    '.' => continue,
+                    '#' => None,
+                    '^' => Some(Direction::Up),
+                    '>' => Some(Direction::Right),
+                    'v' => Some(Direction::Down),
+                    '<' => Some(Direction::Left),
+                    'X' => return Err(ScaffoldError::Tumbling),
+                    _ => return Err(ScaffoldError::InvalidPixel { x, row, pixel }),
+                };
+                scaffold.insert(point);
+                if let Some(facing) = facing {
+                    robot = Some((point, facing));
+                }
+            }
+        }
+        let (robot, facing) = robot.ok_or(ScaffoldError::NoRobot)?;
+        Ok(Camera {
+            scaffold,
+            robot,
+            facing,
+        })
+    }
+}
+
+impl Camera {
+    pub fn is_scaffold(&self, point: Point) -> bool {
+        self.scaffold.contains(&point)
+    }
+
+    pub fn intersections(&self) -> Vec<Point> {
+        let mut points: Vec<Point> = self
+            .scaffold
+            .iter()
+            .copied()
+            .filter(|point| point.neighbors().iter().all(|&n| self.is_scaffold(n)))
+            .collect();
+        points.sort_by_key(|point| (-point.y, point.x));
+        points
+    }
+
+    // The sum of the intersections' distance from the left edge times their
+    // distance from the top edge.
+    pub fn alignment(&self) -> i64 {
+        self.intersections().iter().map(|p| p.x * -p.y).sum()
+    }
+
+    // The robot's way along the scaffold, going straight over intersections
+    // and turning only where it has to, until it reaches an end. On a closed
+    // ring it stops once it's back where it started, facing the same way.
+    pub fn path(&self) -> Vec<Step> {
+        let mut steps = vec![];
+        let (mut at, mut facing) = (self.robot, self.facing);
+        let mut seen = HashSet::new();
+        while seen.insert((at, facing)) {
+            let ahead = at.step(facing);
+            if self.is_scaffold(ahead) {
+                at = ahead;
+                match steps.last_mut() {
+                    Some(Step::Forward(n)) => *n += 1,
+                    _ => steps.push(Step::Forward(1)),
+                }
+            } else if self.is_scaffold(at.step(facing.turn_left())) {
+                facing = facing.turn_left();
+                steps.push(Step::Left);
+            } else if self.is_scaffold(at.step(facing.turn_right())) {
+                facing = facing.turn_right();
+                steps.push(Step::Right);
+            } else {
+                break;
+            }
+        }
+        steps
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Movement {
+    // Indices into `functions`.
+    pub main: Vec<usize>,
+    pub functions: Vec<Vec<Step>>,
+}
+
+impl Movement {
+    pub fn expand(&self) -> Vec<Step> {
+        let calls = self.main.iter().map(|&i| &self.functions[i]);
+        calls.flatten().copied().collect()
+    }
+}
+
+// The main routine and each function on a line, as the robot takes them.
+// Unused functions are left empty.
+impl fmt::Display for Movement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let calls: Vec<String> = self
+            .main
+            .iter()
+            .map(|&i| FUNCTIONS[i].to_string())
+            .collect();
+        writeln!(f, "{}", calls.join(","))?;
+        for i in 0..FUNCTIONS.len() {
+            writeln!(
+                f,
+                "{}",
+                self.functions.get(i).map_or(String::new(), |s| routine(s))
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn fits(routine: usize) -> bool {
+    // Calls or steps are separated by commas.
+    2 * routine - 1 <= MAX_ROUTINE
+}
+
+fn cover<'a>(path: &'a [Step], functions: &mut Vec<&'a [Step]>, main: &mut Vec<usize>) -> bool {
+    if path.is_empty() {
+        return true;
+    }
+    if !fits(main.len() + 1) {
+        return false;
+    }
+    for i in 0..functions.len() {
+        if path.starts_with(functions[i]) {
+            main.push(i);
+            if cover(&path[functions[i].len()..], functions, main) {
+                return true;
+            }
+            main.pop();
+        }
+    }
+    if functions.len() < FUNCTIONS.len() {
+        // Longest first, since longer functions leave less to cover.
+        let longest = (1..=path.len())
+            .take_while(|&n| routine(&path[..n]).len() <= MAX_ROUTINE)
+            .last()
+            .unwrap_or(0);
+        for n in (1..=longest).rev() {
+            functions.push(&path[..n]);
+            main.push(functions.len() - 1);
+            if cover(&path[n..], functions, main) {
+                return true;
+            }
+            main.pop();
+            functions.pop();
+        }
+    }
+    false
+}
+
+// Splits the path into up to three functions called from a main routine,
+// all within `MAX_ROUTINE` characters.
+pub fn compress(path: &[Step]) -> Option<Movement> {
+    let (mut functions, mut main) = (vec![], vec![]);
+    if !cover(path, &mut functions, &mut main) {
+        return None;
+    }
+    Some(Movement {
+        main,
+        functions: functions.into_iter().map(<[Step]>::to_vec).collect(),
+    })
+}
+
+pub fn camera(program: Vec<i64>) -> Result<Camera, ScaffoldError> {
+    let reply = Ascii::new(program).run()?;
+    reply.text.parse()
+}
+
+// Wakes the robot and sends it along the movement routine, without the
+// video feed.
+pub fn collect_dust(program: Vec<i64>, movement: &Movement) -> Result<i64, ScaffoldError> {
+    if program.is_empty() {
+        return Err(ScaffoldError::Asleep);
+    }
+    let mut machine = Machine::new(program).growable();
+    machine.memory_mut().set(0, 2);
+    let mut robot = Ascii::from_machine(machine);
+    robot.run()?;
+    robot.send(&movement.to_string());
+    robot.send_line("n");
+    let reply = robot.run()?;
+    match reply.values.last() {
+        Some(&dust) if reply.halted => Ok(dust),
+        _ => Err(ScaffoldError::NoDust(reply.text)),
+    }
+}
+
+pub fn solve_first(s: &str) -> i64 {
+    camera(loader::parse(s).unwrap()).unwrap().alignment()
+}
+
+pub fn solve_second(s: &str) -> i64 {
+    let program: Vec<i64> = loader::parse(s).unwrap();
+    let path = camera(program.clone()).unwrap().path();
+    let movement = compress(&path).expect("path doesn't fit in three functions");
+    collect_dust(program, &movement).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CROSSINGS: &str = "\
+..#..........
+..#..........
+#######...###
+#.#...#...#.#
+#############
+..#...#...#..
+..#####...^..
+";
+
+    const LOOPS: &str = "\
+#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......
+";
+
+    // A robot on the scaffold in `image`. Asleep, it prints the image.
+    // Woken, it reads the movement routine and the video feed answer, five
+    // lines in all, and reports 1000 plus the number of characters read.
+    fn robot(image: &str) -> Vec<i64> {
+        let mut program = vec![
+            1, 41, 42, 43, // 0: asleep = 1 + 0, or 0 when woken to 1 * 0
+            1006, 43, 17, // 4: if !asleep goto 17
+            109, 48, // 7: rb = image
+            204, 0, // 9: output rb[0]
+            109, 1, // 11: rb += 1
+            1205, 0, 9,  // 13: if rb[0] goto 9
+            99, // 16
+            3, 44, // 17: c = input
+            1001, 45, 1, 45, // 19: read += 1
+            1008, 44, 10, 47, // 23: newline = c == '\n'
+            1, 46, 47, 46, // 27: lines += newline
+            1007, 46, 5, 47, // 31: more = lines < 5
+            1005, 47, 17, // 35: if more goto 17
+            4, 45, // 38: output read
+            99, // 40
+            1, 0, 0, // 41: 1, 0, asleep
+            0, 1000, 0, 0, // 44: c, read, lines, newline/more
+        ];
+        program.extend(image.bytes().map(i64::from));
+        program.push(0);
+        program
+    }
+
+    #[test]
+    fn aligns_the_camera() {
+        let view: Camera = CROSSINGS.parse().unwrap();
+        let intersections: Vec<(i64, i64)> =
+            view.intersections().iter().map(|p| (p.x, -p.y)).collect();
+        assert_eq!(intersections, [(2, 2), (2, 4), (6, 4), (10, 4)]);
+        assert_eq!(view.alignment(), 76);
+
+        assert_eq!(camera(robot(CROSSINGS)), Ok(view));
+    }
+
+    #[test]
+    fn rejects_bad_images() {
+        assert_eq!("..#\n..#\n".parse::<Camera>(), Err(ScaffoldError::NoRobot));
+        assert_eq!("#X#\n".parse::<Camera>(), Err(ScaffoldError::Tumbling));
+        assert_eq!(
+            "#^#\n#?#\n".parse::<Camera>(),
+            Err(ScaffoldError::InvalidPixel {
+                x: 1,
+                row: 1,
+                pixel: '?'
+            })
+        );
+    }
+
+    #[test]
+    fn follows_the_scaffold() {
+        let camera: Camera = LOOPS.parse().unwrap();
+        assert_eq!(
+            routine(&camera.path()),
+            "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+        );
+
+        let ring: Camera = "^##\n#.#\n###\n".parse().unwrap();
+        assert_eq!(routine(&ring.path()), "R,2,R,2,R,2,R,2");
+    }
+
+    #[test]
+    fn compresses_paths() {
+        let path = LOOPS.parse::<Camera>().unwrap().path();
+        let movement = compress(&path).unwrap();
+        assert_eq!(movement.expand(), path);
+        for line in movement.to_string().lines() {
+            assert!(line.len() <= MAX_ROUTINE, "{}", line);
+        }
+        assert_eq!(movement.to_string().lines().count(), 4);
+
+        // Nothing repeats, so three functions can't cover it.
+        let path: Vec<Step> = (1..40)
+            .flat_map(|n| [Step::Left, Step::Forward(n)])
+            .collect();
+        assert!(compress(&path).is_none());
+        let movement = compress(&[Step::Left; 30]).unwrap();
+        assert_eq!(movement.to_string(), "A,A,A\nL,L,L,L,L,L,L,L,L,L\n\n\n");
+    }
+
+    #[test]
+    fn collects_dust() {
+        let program = robot(LOOPS);
+        let path = camera(program.clone()).unwrap().path();
+        let movement = compress(&path).unwrap();
+        let sent = movement.to_string().len() as i64 + "n\n".len() as i64;
+        assert_eq!(collect_dust(program, &movement), Ok(1000 + sent));
+
+        assert_eq!(collect_dust(vec![], &movement), Err(ScaffoldError::Asleep));
+        // Woken, it multiplies instead of adding, then halts.
+        assert_eq!(
+            collect_dust(vec![1, 0, 0, 0, 104, 63, 99], &movement),
+            Err(ScaffoldError::NoDust(String::new()))
+        );
+    }
+}
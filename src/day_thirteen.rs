@@ -0,0 +1,426 @@
+// The arcade cabinet. The game program draws by outputting `x, y, tile`
+// triples, with the score sent as `-1, 0, score`, and reads the joystick
+// whenever it wants to advance a frame.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use crate::intcode::{loader, Error, Machine, State};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Tile {
+    Empty = 0,
+    Wall = 1,
+    Block = 2,
+    Paddle = 3,
+    Ball = 4,
+}
+
+impl TryFrom<i64> for Tile {
+    type Error = ();
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Tile::Empty),
+            1 => Ok(Tile::Wall),
+            2 => Ok(Tile::Block),
+            3 => Ok(Tile::Paddle),
+            4 => Ok(Tile::Ball),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Tile {
+    fn symbol(self) -> char {
+        match self {
+            Tile::Empty => ' ',
+            Tile::Wall => '#',
+            Tile::Block => '+',
+            Tile::Paddle => '=',
+            Tile::Ball => 'o',
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Joystick {
+    Left = -1,
+    Neutral = 0,
+    Right = 1,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Screen {
+    // Keyed by (y, x) so rows come out in order.
+    tiles: BTreeMap<(i64, i64), Tile>,
+    pub score: i64,
+}
+
+impl Screen {
+    pub fn tile(&self, x: i64, y: i64) -> Tile {
+        self.tiles.get(&(y, x)).copied().unwrap_or(Tile::Empty)
+    }
+
+    pub fn count(&self, tile: Tile) -> usize {
+        self.tiles.values().filter(|t| **t == tile).count()
+    }
+
+    // Where a tile is drawn, if it's drawn exactly once.
+    pub fn find(&self, tile: Tile) -> Option<(i64, i64)> {
+        let mut found = self.tiles.iter().filter(|(_, t)| **t == tile);
+        match (found.next(), found.next()) {
+            (Some((&(y, x), _)), None) => Some((x, y)),
+            _ => None,
+        }
+    }
+
+    // Clears the terminal and draws the frame over it.
+    pub fn draw(&self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(out, "\x1b[H\x1b[2J{}", self)?;
+        out.flush()
+    }
+}
+
+// The frame buffer: the score, then every row from the top left tile drawn to
+// the bottom right one.
+impl fmt::Display for Screen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Score: {}", self.score)?;
+        let (ys, xs): (Vec<i64>, Vec<i64>) = self.tiles.keys().copied().unzip();
+        let (Some(left), Some(right)) = (xs.iter().min(), xs.iter().max()) else {
+            return Ok(());
+        };
+        for y in ys[0]..=ys[ys.len() - 1] {
+            let row: String = (*left..=*right).map(|x| self.tile(x, y).symbol()).collect();
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ArcadeError {
+    Machine(Error<i64>),
+    InvalidTile { x: i64, y: i64, value: i64 },
+    // The game halted partway through drawing a tile.
+    Truncated,
+    // The game ended with blocks left.
+    Lost { score: i64, blocks: usize },
+    // The program is empty, so there's no address 0 to insert quarters at.
+    NoCoinSlot,
+}
+
+impl fmt::Display for ArcadeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArcadeError::Machine(e) => write!(f, "arcade crashed: {}", e),
+            ArcadeError::InvalidTile { x, y, value } => {
+                write!(f, "invalid tile {} at ({}, {})", value, x, y)
+            }
+            ArcadeError::Truncated => write!(f, "game halted partway through a tile"),
+            ArcadeError::Lost { score, blocks } => {
+                write!(
+                    f,
+                    "lost with {} blocks left and a score of {}",
+                    blocks, score
+                )
+            }
+            ArcadeError::NoCoinSlot => write!(f, "empty program has nowhere to insert quarters"),
+        }
+    }
+}
+
+impl std::error::Error for ArcadeError {}
+
+impl From<Error<i64>> for ArcadeError {
+    fn from(e: Error<i64>) -> Self {
+        ArcadeError::Machine(e)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Status {
+    WaitingForJoystick,
+    GameOver,
+}
+
+#[derive(Debug, Clone)]
+pub struct Arcade {
+    machine: Machine,
+    screen: Screen,
+}
+
+impl Arcade {
+    pub fn new(program: Vec<i64>) -> Self {
+        Arcade {
+            machine: Machine::new(program).growable(),
+            screen: Screen::default(),
+        }
+    }
+
+    // Inserts quarters, so the game can be played instead of only drawn.
+    pub fn free_play(mut self) -> Result<Self, ArcadeError> {
+        if self.machine.memory().is_empty() {
+            return Err(ArcadeError::NoCoinSlot);
+        }
+        self.machine.memory_mut().set(0, 2);
+        Ok(self)
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    pub fn tilt(&mut self, joystick: Joystick) {
+        self.machine.push_input(joystick as i64);
+    }
+
+    // Draws everything the game outputs until it wants the joystick or ends.
+    pub fn run(&mut self) -> Result<Status, ArcadeError> {
+        let mut triple = vec![];
+        loop {
+            let status = match self.machine.run()? {
+                State::Output(value) => {
+                    triple.push(value);
+                    if let [x, y, value] = triple[..] {
+                        self.draw(x, y, value)?;
+                        triple.clear();
+                    }
+                    continue;
+                }
+                State::NeedsInput => Status::WaitingForJoystick,
+                State::Halted => Status::GameOver,
+                State::Vetoed => unreachable!(),
+            };
+            if !triple.is_empty() {
+                return Err(ArcadeError::Truncated);
+            }
+            return Ok(status);
+        }
+    }
+
+    fn draw(&mut self, x: i64, y: i64, value: i64) -> Result<(), ArcadeError> {
+        if (x, y) == (-1, 0) {
+            self.screen.score = value;
+            return Ok(());
+        }
+        let tile = Tile::try_from(value).map_err(|_| ArcadeError::InvalidTile { x, y, value })?;
+        self.screen.tiles.insert((y, x), tile);
+        Ok(())
+    }
+}
+
+// Plays by keeping the paddle under the ball, showing each frame to
+// `on_frame`, and returns the final score once every block is broken.
+pub fn autoplay(mut arcade: Arcade, mut on_frame: impl FnMut(&Screen)) -> Result<i64, ArcadeError> {
+    loop {
+        let status = arcade.run()?;
+        on_frame(arcade.screen());
+        if status == Status::GameOver {
+            break;
+        }
+        let ball = arcade.screen.find(Tile::Ball).map(|(x, _)| x);
+        let paddle = arcade.screen.find(Tile::Paddle).map(|(x, _)| x);
+        let joystick = match (ball, paddle) {
+            (Some(ball), Some(paddle)) if ball < paddle => Joystick::Left,
+            (Some(ball), Some(paddle)) if ball > paddle => Joystick::Right,
+            _ => Joystick::Neutral,
+        };
+        arcade.tilt(joystick);
+    }
+
+    let screen = arcade.screen();
+    match screen.count(Tile::Block) {
+        0 => Ok(screen.score),
+        blocks => Err(ArcadeError::Lost {
+            score: screen.score,
+            blocks,
+        }),
+    }
+}
+
+// Plays on the terminal, reading a line per frame: `a` or `h` moves left, `d`
+// or `l` moves right, and anything else holds still.
+pub fn play(
+    mut arcade: Arcade,
+    input: impl BufRead,
+    out: &mut impl Write,
+) -> Result<i64, Box<dyn std::error::Error>> {
+    let mut lines = input.lines();
+    while arcade.run()? == Status::WaitingForJoystick {
+        arcade.screen().draw(out)?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        arcade.tilt(match line.trim() {
+            "a" | "h" => Joystick::Left,
+            "d" | "l" => Joystick::Right,
+            _ => Joystick::Neutral,
+        });
+    }
+    arcade.screen().draw(out)?;
+    Ok(arcade.screen().score)
+}
+
+pub fn solve_first(s: &str) -> usize {
+    let mut arcade = Arcade::new(loader::parse(s).unwrap());
+    arcade.run().unwrap();
+    arcade.screen().count(Tile::Block)
+}
+
+pub fn solve_second(s: &str) -> i64 {
+    let arcade = Arcade::new(loader::parse(s).unwrap()).free_play().unwrap();
+    autoplay(arcade, |_| {}).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::lang::compile;
+
+    // A small game: a ball bounces between a row of blocks and the paddle,
+    // breaking a block and scoring each time it reaches the top, and the game
+    // is lost if the paddle isn't under the ball when it comes down.
+    const GAME: &str = "
+        fn draw(x, y, tile) { output(x); output(y); output(tile); }
+
+        fn main() {
+            var x = 0;
+            while x < 9 { draw(x, 0, 1); x = x + 1; }
+            var y = 1;
+            while y < 7 { draw(0, y, 1); draw(8, y, 1); y = y + 1; }
+            x = 1;
+            while x < 8 { draw(x, 1, 2); x = x + 1; }
+
+            var blocks = 7;
+            var score = 0;
+            var px = 4;
+            var bx = 3;
+            var by = 2;
+            var dx = 1;
+            var dy = 1;
+            draw(px, 6, 3);
+            draw(bx, by, 4);
+            draw(-1, 0, score);
+
+            while blocks > 0 {
+                var nx = px + input();
+                if nx > 0 && nx < 8 { draw(px, 6, 0); px = nx; draw(px, 6, 3); }
+
+                if by == 5 && dy == 1 {
+                    if px != bx { return; }
+                    dy = -1;
+                }
+                if by == 2 && dy == -1 {
+                    draw(blocks, 1, 0);
+                    blocks = blocks - 1;
+                    score = score + 10 * blocks;
+                    draw(-1, 0, score);
+                    dy = 1;
+                }
+                if bx + dx < 1 || bx + dx > 7 { dx = -dx; }
+                draw(bx, by, 0);
+                bx = bx + dx;
+                by = by + dy;
+                draw(bx, by, 4);
+            }
+        }";
+
+    fn game() -> Arcade {
+        Arcade::new(compile(GAME).unwrap())
+    }
+
+    #[test]
+    fn draws_the_screen() {
+        let mut arcade = game();
+        assert_eq!(arcade.run(), Ok(Status::WaitingForJoystick));
+        let screen = arcade.screen();
+        assert_eq!(screen.count(Tile::Block), 7);
+        assert_eq!(screen.find(Tile::Ball), Some((3, 2)));
+        assert_eq!(screen.find(Tile::Paddle), Some((4, 6)));
+        assert_eq!(screen.find(Tile::Wall), None);
+        assert_eq!(
+            screen.to_string(),
+            "Score: 0\n\
+             #########\n\
+             #+++++++#\n\
+             #  o    #\n\
+             #       #\n\
+             #       #\n\
+             #       #\n\
+             #   =   #\n"
+        );
+    }
+
+    #[test]
+    fn takes_joystick_input() {
+        let mut arcade = game();
+        arcade.run().unwrap();
+        arcade.tilt(Joystick::Left);
+        assert_eq!(arcade.run(), Ok(Status::WaitingForJoystick));
+        assert_eq!(arcade.screen().find(Tile::Paddle), Some((3, 6)));
+        assert_eq!(arcade.screen().find(Tile::Ball), Some((4, 3)));
+
+        // Holding still misses the ball.
+        for _ in 0..2 {
+            arcade.tilt(Joystick::Neutral);
+            arcade.run().unwrap();
+        }
+        arcade.tilt(Joystick::Neutral);
+        assert_eq!(arcade.run(), Ok(Status::GameOver));
+        assert_eq!(arcade.screen().count(Tile::Block), 7);
+    }
+
+    #[test]
+    fn autoplays_to_the_end() {
+        let mut frames = 0;
+        let score = autoplay(game(), |screen| {
+            frames += 1;
+            assert!(screen.find(Tile::Ball).is_some());
+        });
+        assert_eq!(score, Ok(210));
+        assert!(frames > 7 * 6);
+    }
+
+    #[test]
+    fn plays_on_a_terminal() {
+        let input = "d\nd\n\n\n\n".as_bytes();
+        let mut out = vec![];
+        assert_eq!(play(game(), input, &mut out).unwrap(), 0);
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("\x1b[H\x1b[2J").count(), 7);
+        assert!(
+            out.ends_with("#     o #\n#       #\n#       #\n#     = #\n"),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn rejects_bad_output() {
+        let mut arcade = Arcade::new(vec![104, 1, 104, 2, 104, 7, 99]);
+        assert_eq!(
+            arcade.run(),
+            Err(ArcadeError::InvalidTile {
+                x: 1,
+                y: 2,
+                value: 7
+            })
+        );
+        let mut arcade = Arcade::new(vec![104, 1, 104, 2, 99]);
+        assert_eq!(arcade.run(), Err(ArcadeError::Truncated));
+    }
+
+    #[test]
+    fn inserts_quarters() {
+        let arcade = Arcade::new(vec![1, 0, 0, 0, 99]).free_play().unwrap();
+        assert_eq!(arcade.machine.memory()[0], 2);
+        assert!(matches!(
+            Arcade::new(vec![]).free_play(),
+            Err(ArcadeError::NoCoinSlot)
+        ));
+    }
+}
@@ -3,13 +3,7 @@
 use std::collections::{HashMap, HashSet};
 use std::vec::Vec;
 
-#[derive(Debug, PartialEq)]
-pub enum Direction {
-    Up,
-    Right,
-    Left,
-    Down,
-}
+use crate::geometry::{Direction, Point};
 
 #[derive(Debug, PartialEq)]
 pub struct Command {
@@ -17,79 +11,29 @@ pub struct Command {
     distance: u32,
 }
 
-#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
-pub struct Point {
-    x: i64,
-    y: i64,
-}
-
 impl Command {
     pub fn new(val: &str) -> Command {
         Command {
-            direction: match val.chars().nth(0) {
+            direction: match val.chars().next() {
                 Some('U') => Direction::Up,
                 Some('R') => Direction::Right,
                 Some('D') => Direction::Down,
                 Some('L') => Direction::Left,
                 _ => panic!("Invalid input! {:?}", val),
             },
-            distance: val[1..]
-                .parse()
-                .expect(format!("Failed to parse direction from input string: {:?}", val).as_str()),
+            distance: val[1..].parse().unwrap_or_else(|_| {
+                panic!("Failed to parse direction from input string: {:?}", val)
+            }),
         }
     }
 
     pub fn travel(&self, start: Point, points: &mut HashSet<Point>) -> Point {
-        let end = match self.direction {
-            Direction::Up => Point {
-                x: start.x,
-                y: start.y + i64::from(self.distance),
-            },
-            Direction::Right => Point {
-                x: start.x + i64::from(self.distance),
-                y: start.y,
-            },
-            Direction::Down => Point {
-                x: start.x,
-                y: start.y - i64::from(self.distance),
-            },
-            Direction::Left => Point {
-                x: start.x - i64::from(self.distance),
-                y: start.y,
-            },
-        };
-
-        let start_range = match self.direction {
-            Direction::Right => start.x + 1,
-            Direction::Left => start.x,
-            Direction::Up => start.y + 1,
-            Direction::Down => start.y,
-        };
-
-        let end_range = match self.direction {
-            Direction::Right => end.x,
-            Direction::Left => end.x - 1,
-            Direction::Up => end.y,
-            Direction::Down => end.y - 1,
-        };
-
-        let range = match self.direction {
-            Direction::Right | Direction::Up => start_range..=end_range,
-            Direction::Left | Direction::Down => end_range..=start_range,
-        };
-
-        for x in range {
-            let start_range = match self.direction {
-                Direction::Right | Direction::Left => {
-                    points.insert(Point { x, y: start.y });
-                }
-                Direction::Up | Direction::Down => {
-                    points.insert(Point { x: start.x, y: x });
-                }
-            };
+        let mut point = start;
+        for _ in 0..self.distance {
+            point = point.step(self.direction);
+            points.insert(point);
         }
-
-        end
+        point
     }
 
     pub fn travel_with_steps(
@@ -98,79 +42,21 @@ impl Command {
         mut steps: u32,
         points: &mut HashMap<Point, u32>,
     ) -> (Point, u32) {
-        let end = match self.direction {
-            Direction::Up => Point {
-                x: start.x,
-                y: start.y + i64::from(self.distance),
-            },
-            Direction::Right => Point {
-                x: start.x + i64::from(self.distance),
-                y: start.y,
-            },
-            Direction::Down => Point {
-                x: start.x,
-                y: start.y - i64::from(self.distance),
-            },
-            Direction::Left => Point {
-                x: start.x - i64::from(self.distance),
-                y: start.y,
-            },
-        };
-
-        let (start_range, end_range) = match self.direction {
-            Direction::Right => (start.x + 1, end.x),
-            Direction::Left => (start.x - 1, end.x),
-            Direction::Up => (start.y + 1, end.y),
-            Direction::Down => (start.y - 1, end.y),
-        };
-
-        let range: Box::<dyn Iterator<Item = i64>> = match self.direction {
-            Direction::Right | Direction::Up => 
-                Box::new(start_range..=end_range),
-            Direction::Left | Direction::Down => 
-                Box::new((end_range..=start_range).rev()),
-        };
-        // Example:
-        // Left: 4, starting at x: 1, y: 3 -> x: -3, y: 3
-        // 0, -1, -2, -3
-
-        for x in range {
+        let mut point = start;
+        for _ in 0..self.distance {
+            point = point.step(self.direction);
             steps += 1;
-            match self.direction {
-                Direction::Right | Direction::Left => {
-                    let point = Point { x, y: start.y };
-                    if points.get(&point).is_none() {
-                        points.insert(point, steps);
-                    };
-                }
-                Direction::Up | Direction::Down => {
-                    let point = Point { x: start.x, y: x };
-                    if points.get(&point).is_none() {
-                        points.insert(point, steps);
-                    };
-                }
-            };
+            points.entry(point).or_insert(steps);
         }
-
-        (end, steps)
+        (point, steps)
     }
 }
 
 pub fn parse_input(input: &str) -> (Vec<Command>, Vec<Command>) {
     let mut lines = input.lines();
 
-    let first_cmds: Vec<Command> = lines
-        .nth(0)
-        .unwrap()
-        .split(",")
-        .map(|s| Command::new(s))
-        .collect();
-    let second_cmds: Vec<Command> = lines
-        .nth(0)
-        .unwrap()
-        .split(",")
-        .map(|s| Command::new(s))
-        .collect();
+    let first_cmds: Vec<Command> = lines.next().unwrap().split(',').map(Command::new).collect();
+    let second_cmds: Vec<Command> = lines.next().unwrap().split(',').map(Command::new).collect();
 
     (first_cmds, second_cmds)
 }
@@ -178,7 +64,7 @@ pub fn parse_input(input: &str) -> (Vec<Command>, Vec<Command>) {
 pub fn solve_first(input: &str) -> i64 {
     let (first_cmds, second_cmds) = parse_input(input);
 
-    let mut starting = Point { x: 0, y: 0 };
+    let mut starting = Point::ORIGIN;
     let mut first_points = HashSet::new();
 
     for cmd in first_cmds {
@@ -186,7 +72,7 @@ pub fn solve_first(input: &str) -> i64 {
         starting = new_starting;
     }
 
-    let mut starting = Point { x: 0, y: 0 };
+    let mut starting = Point::ORIGIN;
     let mut second_points = HashSet::new();
 
     for cmd in second_cmds {
@@ -196,8 +82,7 @@ pub fn solve_first(input: &str) -> i64 {
 
     let mut distances: Vec<_> = first_points
         .intersection(&second_points)
-        .into_iter()
-        .map(|Point { x, y }| x.abs() + y.abs())
+        .map(|point| point.distance())
         .collect();
 
     if distances.is_empty() {
@@ -211,7 +96,7 @@ pub fn solve_first(input: &str) -> i64 {
 pub fn solve_second(input: &str) -> u32 {
     let (first_cmds, second_cmds) = parse_input(input);
 
-    let mut start = Point { x: 0, y: 0 };
+    let mut start = Point::ORIGIN;
     let mut steps = 0;
     let mut first_points = HashMap::new();
 
@@ -220,7 +105,7 @@ pub fn solve_second(input: &str) -> u32 {
         start = new_start;
         steps = new_steps;
     }
-    let mut start = Point { x: 0, y: 0 };
+    let mut start = Point::ORIGIN;
     let mut steps = 0;
     let mut second_points = HashMap::new();
 
@@ -251,7 +136,6 @@ U62,R66,U55,R34,D71,R55,D58,R83";
 
     const SAMPLE_THREE: &str = "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51
 U98,R91,D20,R16,D67,R40,U7,R15,U6,R7";
-    
 
     #[test]
     fn char_into_int() {
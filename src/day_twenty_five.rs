@@ -0,0 +1,559 @@
+// The cryostasis droid. The droid's program is a text adventure: it describes
+// each room it enters and takes commands like `north` or `take mug`. The ship
+// is mapped breadth first from snapshots of the droid, as in day 15, then one
+// droid collects every item that's safe to carry and tries combinations of
+// them on the pressure-sensitive floor until it's let through.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::geometry::Direction;
+use crate::intcode::ascii::{Ascii, Reply};
+use crate::intcode::{loader, Error};
+
+// Items that can't be tried on a snapshot, because taking them never returns.
+pub const DEADLY: [&str; 1] = ["infinite loop"];
+
+#[derive(Debug, PartialEq)]
+pub enum AdventureError {
+    Machine(Error<i64>),
+    // The droid said something that isn't a room where one was expected.
+    Unreadable(String),
+    UnknownDoor(String),
+    // The droid halted somewhere other than the pressure-sensitive floor.
+    Halted(String),
+    NoCheckpoint,
+    NoPath(String),
+    // Too many items to try every combination of.
+    TooManyItems(usize),
+    NoCombination,
+}
+
+impl fmt::Display for AdventureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AdventureError::Machine(e) => write!(f, "droid crashed: {}", e),
+            AdventureError::Unreadable(text) => write!(f, "unreadable droid output {:?}", text),
+            AdventureError::UnknownDoor(door) => write!(f, "unknown door {:?}", door),
+            AdventureError::Halted(text) => write!(f, "droid halted: {:?}", text),
+            AdventureError::NoCheckpoint => write!(f, "no pressure-sensitive floor found"),
+            AdventureError::NoPath(room) => write!(f, "no way to {}", room),
+            AdventureError::TooManyItems(count) => write!(f, "too many items to try: {}", count),
+            AdventureError::NoCombination => write!(f, "no combination of items is let through"),
+        }
+    }
+}
+
+impl std::error::Error for AdventureError {}
+
+impl From<Error<i64>> for AdventureError {
+    fn from(e: Error<i64>) -> Self {
+        AdventureError::Machine(e)
+    }
+}
+
+fn door_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "north",
+        Direction::Down => "south",
+        Direction::Left => "west",
+        Direction::Right => "east",
+    }
+}
+
+fn door(name: &str) -> Result<Direction, AdventureError> {
+    Direction::ALL
+        .into_iter()
+        .find(|&direction| door_name(direction) == name)
+        .ok_or_else(|| AdventureError::UnknownDoor(name.to_string()))
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Room {
+    pub name: String,
+    pub description: String,
+    pub doors: Vec<Direction>,
+    pub items: Vec<String>,
+}
+
+// Every room described in `text`, in order. Being pushed back from the
+// pressure-sensitive floor describes both the floor and the checkpoint.
+pub fn rooms(text: &str) -> Result<Vec<Room>, AdventureError> {
+    let mut rooms: Vec<Room> = vec![];
+    let mut list = None;
+    for line in text.lines() {
+        if let Some(name) = line.strip_prefix("== ").and_then(|l| l.strip_suffix(" ==")) {
+            rooms.push(Room {
+                name: name.to_string(),
+                description: String::new(),
+                doors: vec![],
+                items: vec![],
+            });
+            list = None;
+            continue;
+        }
+        let Some(room) = rooms.last_mut() else {
+            continue;
+        };
+        match (line, line.strip_prefix("- ")) {
+            ("Doors here lead:", _) => list = Some(true),
+            ("Items here:", _) => list = Some(false),
+            (_, Some(entry)) => match list {
+                Some(true) => room.doors.push(door(entry)?),
+                Some(false) => room.items.push(entry.to_string()),
+                None => return Err(AdventureError::Unreadable(text.to_string())),
+            },
+            ("", None) => list = None,
+            (_, None) if room.doors.is_empty() && room.description.is_empty() => {
+                room.description = line.to_string()
+            }
+            _ => {}
+        }
+    }
+    Ok(rooms)
+}
+
+fn command(droid: &mut Ascii, command: &str) -> Result<Reply, AdventureError> {
+    droid.send_line(command);
+    Ok(droid.run()?)
+}
+
+// Takes the item on a snapshot, and checks the droid survives and can still
+// leave the room.
+fn safe(droid: &Ascii, room: &Room, item: &str) -> Result<bool, AdventureError> {
+    if DEADLY.contains(&item) {
+        return Ok(false);
+    }
+    let mut droid = droid.clone();
+    if command(&mut droid, &format!("take {}", item))?.halted {
+        return Ok(false);
+    }
+    let Some(&direction) = room.doors.first() else {
+        return Ok(true);
+    };
+    let reply = command(&mut droid, door_name(direction))?;
+    Ok(!reply.halted && !rooms(&reply.text)?.is_empty())
+}
+
+#[derive(Debug, Clone)]
+pub struct Ship {
+    pub start: String,
+    pub rooms: HashMap<String, Room>,
+    // Where each door leads, by the room it's in.
+    pub doors: HashMap<(String, Direction), String>,
+    // The room before the pressure-sensitive floor, and the door to it.
+    pub checkpoint: Option<(String, Direction)>,
+    // Safe items, with the room they're in, in the order they were found.
+    pub items: Vec<(String, String)>,
+    pub deadly: Vec<String>,
+}
+
+impl Ship {
+    // The doors to go through from one room to another.
+    pub fn path(&self, from: &str, to: &str) -> Option<Vec<Direction>> {
+        let mut previous: HashMap<&str, Option<(&str, Direction)>> = HashMap::from([(from, None)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(room) = queue.pop_front() {
+            if room == to {
+                let mut path = vec![];
+                let mut at = room;
+                while let Some(&Some((back, direction))) = previous.get(at) {
+                    path.push(direction);
+                    at = back;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let Some(here) = self.rooms.get(room) else {
+                continue;
+            };
+            for &direction in &here.doors {
+                let Some(next) = self.doors.get(&(room.to_string(), direction)) else {
+                    continue;
+                };
+                if !previous.contains_key(next.as_str()) {
+                    previous.insert(next.as_str(), Some((room, direction)));
+                    queue.push_back(next.as_str());
+                }
+            }
+        }
+        None
+    }
+}
+
+// Maps the ship, and returns the droid as it was before leaving the first
+// room.
+pub fn explore(program: Vec<i64>) -> Result<(Ship, Ascii), AdventureError> {
+    let mut droid = Ascii::new(program);
+    let reply = droid.run()?;
+    let Some(start) = rooms(&reply.text)?.pop() else {
+        return Err(AdventureError::Unreadable(reply.text));
+    };
+    let mut ship = Ship {
+        start: start.name.clone(),
+        rooms: HashMap::new(),
+        doors: HashMap::new(),
+        checkpoint: None,
+        items: vec![],
+        deadly: vec![],
+    };
+    let mut queue = VecDeque::new();
+    let mut seen = HashSet::new();
+    queue.push_back((start, droid.clone()));
+
+    while let Some((room, snapshot)) = queue.pop_front() {
+        if !seen.insert(room.name.clone()) {
+            continue;
+        }
+        for item in &room.items {
+            match safe(&snapshot, &room, item)? {
+                true => ship.items.push((room.name.clone(), item.clone())),
+                false => ship.deadly.push(item.clone()),
+            }
+        }
+        for &direction in &room.doors {
+            if ship.doors.contains_key(&(room.name.clone(), direction)) {
+                continue;
+            }
+            let mut moved = snapshot.clone();
+            let reply = command(&mut moved, door_name(direction))?;
+            if reply.halted {
+                return Err(AdventureError::Halted(reply.text));
+            }
+            let mut entered = rooms(&reply.text)?;
+            match entered.len() {
+                1 => {}
+                2 => {
+                    ship.checkpoint = Some((room.name.clone(), direction));
+                    continue;
+                }
+                _ => return Err(AdventureError::Unreadable(reply.text)),
+            }
+            let next = entered.remove(0);
+            ship.doors
+                .insert((room.name.clone(), direction), next.name.clone());
+            ship.doors
+                .insert((next.name.clone(), direction.reverse()), room.name.clone());
+            queue.push_back((next, moved));
+        }
+        ship.rooms.insert(room.name.clone(), room);
+    }
+    Ok((ship, droid))
+}
+
+fn travel(droid: &mut Ascii, ship: &Ship, from: &str, to: &str) -> Result<(), AdventureError> {
+    let path = ship
+        .path(from, to)
+        .ok_or_else(|| AdventureError::NoPath(to.to_string()))?;
+    for direction in path {
+        let reply = command(droid, door_name(direction))?;
+        if reply.halted {
+            return Err(AdventureError::Halted(reply.text));
+        }
+    }
+    Ok(())
+}
+
+enum Weighing {
+    Passed(i64),
+    TooLight,
+    TooHeavy,
+}
+
+fn weigh(droid: &mut Ascii, direction: Direction) -> Result<Weighing, AdventureError> {
+    let reply = command(droid, door_name(direction))?;
+    if reply.text.contains("heavier than the detected") {
+        return Ok(Weighing::TooLight);
+    }
+    if reply.text.contains("lighter than the detected") {
+        return Ok(Weighing::TooHeavy);
+    }
+    let password = reply
+        .text
+        .split("typing ")
+        .nth(1)
+        .and_then(|rest| rest.split(' ').next())
+        .and_then(|digits| digits.parse().ok());
+    match (reply.halted, password) {
+        (true, Some(password)) => Ok(Weighing::Passed(password)),
+        _ => Err(AdventureError::Unreadable(reply.text)),
+    }
+}
+
+// Every subset of `count` items as bits, in Gray code order.
+fn subsets(count: usize) -> Result<impl Iterator<Item = u32>, AdventureError> {
+    if count >= u32::BITS as usize {
+        return Err(AdventureError::TooManyItems(count));
+    }
+    Ok((0..1u32 << count).map(|i| i ^ (i >> 1)))
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Unlocked {
+    pub password: i64,
+    pub carried: Vec<String>,
+    pub attempts: usize,
+}
+
+// Collects every safe item, then stands at the checkpoint trying subsets of
+// them in Gray code order, so consecutive attempts mostly differ by a single
+// item. A subset too heavy rules out everything containing it, and
+// one too light everything it contains.
+pub fn unlock(program: Vec<i64>) -> Result<Unlocked, AdventureError> {
+    let (ship, mut droid) = explore(program)?;
+    let Some((checkpoint, floor)) = ship.checkpoint.clone() else {
+        return Err(AdventureError::NoCheckpoint);
+    };
+
+    let mut at = ship.start.as_str();
+    for (room, item) in &ship.items {
+        travel(&mut droid, &ship, at, room)?;
+        command(&mut droid, &format!("take {}", item))?;
+        at = room;
+    }
+    travel(&mut droid, &ship, at, &checkpoint)?;
+
+    let items: Vec<&String> = ship.items.iter().map(|(_, item)| item).collect();
+    let subsets = subsets(items.len())?;
+    let mut held: u32 = (1 << items.len()) - 1;
+    let (mut heavy, mut light) = (vec![], vec![]);
+    let mut attempts = 0;
+    for subset in subsets {
+        if heavy.iter().any(|h| h & !subset == 0) || light.iter().any(|l| subset & !l == 0) {
+            continue;
+        }
+        for (bit, item) in items.iter().enumerate() {
+            let verb = match (held >> bit & 1, subset >> bit & 1) {
+                (1, 0) => "drop",
+                (0, 1) => "take",
+                _ => continue,
+            };
+            command(&mut droid, &format!("{} {}", verb, item))?;
+        }
+        held = subset;
+        attempts += 1;
+        match weigh(&mut droid, floor)? {
+            Weighing::Passed(password) => {
+                let carried = items
+                    .iter()
+                    .enumerate()
+                    .filter(|(bit, _)| subset >> bit & 1 == 1);
+                return Ok(Unlocked {
+                    password,
+                    carried: carried.map(|(_, item)| item.to_string()).collect(),
+                    attempts,
+                });
+            }
+            Weighing::TooHeavy => heavy.push(subset),
+            Weighing::TooLight => light.push(subset),
+        }
+    }
+    Err(AdventureError::NoCombination)
+}
+
+pub fn solve_first(s: &str) -> i64 {
+    unlock(loader::parse(s).unwrap()).unwrap().password
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::lang::compile;
+
+    // A small ship: the kitchen is north of the hull breach and a hallway
+    // east of it, with the checkpoint north of the hallway and the
+    // pressure-sensitive floor east of that. Items are in the room they're
+    // found in, or -1 once taken, and the floor lets through a weight of
+    // exactly 8.
+    const DROID: &str = r#"
+        var room = 0;
+        var stuck = 0;
+        var mug = 1; // weighs 3
+        var magnet = 1;
+        var photons = 2;
+        var coin = 2; // weighs 5
+        var loop = 2;
+        var boulder = 3; // weighs 9
+
+        fn show() {
+            print("\n\n\n");
+            if room == 0 {
+                print("== Hull Breach ==\nYou got in through a hole in the floor here.\n");
+                print("\nDoors here lead:\n- north\n- east\n");
+            }
+            if room == 1 {
+                print("== Kitchen ==\nEverything's freeze-dried.\n");
+                print("\nDoors here lead:\n- south\n");
+            }
+            if room == 2 {
+                print("== Hallway ==\nThis area has been optimized for something.\n");
+                print("\nDoors here lead:\n- west\n- north\n");
+            }
+            if room == 3 {
+                print("== Security Checkpoint ==\nIn the next room, a pressure-sensitive ");
+                print("floor will verify your identity.\n");
+                print("\nDoors here lead:\n- south\n- east\n");
+            }
+            if room == 4 {
+                print("== Pressure-Sensitive Floor ==\nAnalyzing...\n");
+                print("\nDoors here lead:\n- west\n");
+            }
+            if mug == room || magnet == room || photons == room
+                || coin == room || loop == room || boulder == room {
+                print("\nItems here:\n");
+            }
+            if mug == room { print("- mug\n"); }
+            if magnet == room { print("- giant electromagnet\n"); }
+            if photons == room { print("- photons\n"); }
+            if coin == room { print("- coin\n"); }
+            if loop == room { print("- infinite loop\n"); }
+            if boulder == room { print("- boulder\n"); }
+        }
+
+        // The room through a door, or -1 if there's none.
+        fn through(door) {
+            if room == 0 && door == 'n' { return 1; }
+            if room == 0 && door == 'e' { return 2; }
+            if room == 1 && door == 's' { return 0; }
+            if room == 2 && door == 'w' { return 0; }
+            if room == 2 && door == 'n' { return 3; }
+            if room == 3 && door == 's' { return 2; }
+            if room == 3 && door == 'e' { return 4; }
+            return -1;
+        }
+
+        // Whether the droid made it past the floor.
+        fn go(door) {
+            if through(door) < 0 {
+                print("\nYou can't go that way.\n");
+                return 0;
+            }
+            if stuck {
+                print("\nThe giant electromagnet is stuck to you.  You can't move!!\n");
+                return 0;
+            }
+            room = through(door);
+            show();
+            if room != 4 { return 0; }
+            var weight = 3 * (mug == -1) + 5 * (coin == -1) + 9 * (boulder == -1);
+            if weight == 8 {
+                print("\nA loud, robotic voice says \"Analysis complete! You may proceed.\" ");
+                print("and you enter the cockpit.\n\"Oh, hello! You should be able to get in ");
+                print("by typing 2424 on the keypad at the main airlock.\"\n");
+                return 1;
+            }
+            print("\nA loud, robotic voice says \"Alert! Droids on this ship are ");
+            if weight < 8 { print("heavier"); } else { print("lighter"); }
+            print(" than the detected value!\" and you are ejected back to the checkpoint.\n");
+            room = 3;
+            show();
+            return 0;
+        }
+
+        // Moves the item with the given initial between places. Returns
+        // whether it was there, or -1 if the droid didn't survive.
+        fn carry(item, from, to) {
+            if item == 'm' && mug == from { mug = to; return 1; }
+            if item == 'c' && coin == from { coin = to; return 1; }
+            if item == 'b' && boulder == from { boulder = to; return 1; }
+            if item == 'g' && magnet == from { magnet = to; stuck = to == -1; return 1; }
+            if item == 'p' && photons == from {
+                print("\nIt is suddenly completely dark! You are eaten by a Grue!\n");
+                return -1;
+            }
+            if item == 'i' && loop == from { while 1 {} }
+            return 0;
+        }
+
+        fn main() {
+            show();
+            while 1 {
+                print("\nCommand?\n");
+                // The verb's initial, and the item's after `take ` or `drop `.
+                var verb = input();
+                var item = 0;
+                var read = 1;
+                var c = verb;
+                while c != '\n' {
+                    c = input();
+                    if read == 5 { item = c; }
+                    read = read + 1;
+                }
+                if verb == 't' || verb == 'd' {
+                    var carried = 0;
+                    if verb == 't' {
+                        carried = carry(item, room, -1);
+                    } else {
+                        carried = carry(item, -1, room);
+                    }
+                    if carried < 0 { return; }
+                    if carried { print("\nDone.\n"); } else { print("\nYou can't.\n"); }
+                } else if go(verb) {
+                    return;
+                }
+            }
+        }"#;
+
+    fn droid() -> Vec<i64> {
+        compile(DROID).unwrap()
+    }
+
+    #[test]
+    fn reads_rooms() {
+        let text = "\n\n\n== Hallway ==\nThis area has been optimized for something.\n\n\
+                    Doors here lead:\n- west\n- north\n\nItems here:\n- molten lava\n- coin\n\n\
+                    Command?\n";
+        assert_eq!(
+            rooms(text),
+            Ok(vec![Room {
+                name: "Hallway".to_string(),
+                description: "This area has been optimized for something.".to_string(),
+                doors: vec![Direction::Left, Direction::Up],
+                items: vec!["molten lava".to_string(), "coin".to_string()],
+            }])
+        );
+        assert_eq!(rooms("\nYou take the mug.\n\nCommand?\n"), Ok(vec![]));
+        assert_eq!(
+            rooms("== Attic ==\n\nDoors here lead:\n- up\n"),
+            Err(AdventureError::UnknownDoor("up".to_string()))
+        );
+    }
+
+    #[test]
+    fn maps_the_ship() {
+        let (ship, _) = explore(droid()).unwrap();
+        assert_eq!(ship.start, "Hull Breach");
+        assert_eq!(ship.rooms.len(), 4);
+        assert_eq!(
+            ship.checkpoint,
+            Some(("Security Checkpoint".to_string(), Direction::Right))
+        );
+        let items: Vec<&str> = ship.items.iter().map(|(_, item)| item.as_str()).collect();
+        assert_eq!(items, ["mug", "coin", "boulder"]);
+        assert_eq!(
+            ship.deadly,
+            ["giant electromagnet", "photons", "infinite loop"]
+        );
+        assert_eq!(
+            ship.path("Kitchen", "Security Checkpoint"),
+            Some(vec![Direction::Down, Direction::Right, Direction::Up])
+        );
+        assert_eq!(ship.path("Cockpit", "Kitchen"), None);
+    }
+
+    #[test]
+    fn finds_the_password() {
+        let unlocked = unlock(droid()).unwrap();
+        assert_eq!(unlocked.password, 2424);
+        assert_eq!(unlocked.carried, ["mug", "coin"]);
+        assert!(unlocked.attempts < 8, "{} attempts", unlocked.attempts);
+    }
+
+    #[test]
+    fn bounds_the_combinations() {
+        let order: Vec<u32> = subsets(3).unwrap().collect();
+        assert_eq!(order, [0, 1, 3, 2, 6, 7, 5, 4]);
+        assert!(subsets(31).is_ok());
+        assert!(matches!(subsets(32), Err(AdventureError::TooManyItems(32))));
+    }
+}
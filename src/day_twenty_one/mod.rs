@@ -0,0 +1,225 @@
+// The springdroid. Its program prompts for a springscript, then either walks
+// (or runs) across the hull and reports the hull damage as a value outside
+// ASCII, or draws the frame where the droid fell through.
+
+pub mod springscript;
+
+use std::fmt;
+
+use crate::intcode::ascii::Ascii;
+use crate::intcode::{loader, Error};
+use springscript::{compile, Formula, Mode, Script};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Outcome {
+    Damage(i64),
+    // Everything the droid printed after taking the script.
+    Fell(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SurveyError {
+    Machine(Error<i64>),
+    // The droid asked for more input after taking the script.
+    Waiting,
+}
+
+impl fmt::Display for SurveyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SurveyError::Machine(e) => write!(f, "springdroid crashed: {}", e),
+            SurveyError::Waiting => write!(f, "springdroid is still waiting for input"),
+        }
+    }
+}
+
+impl std::error::Error for SurveyError {}
+
+impl From<Error<i64>> for SurveyError {
+    fn from(e: Error<i64>) -> Self {
+        SurveyError::Machine(e)
+    }
+}
+
+pub fn survey(program: &[i64], script: &Script) -> Result<Outcome, SurveyError> {
+    let mut droid = Ascii::new(program.to_vec());
+    droid.run()?;
+    droid.send(&script.to_string());
+    let reply = droid.run()?;
+    if !reply.halted {
+        return Err(SurveyError::Waiting);
+    }
+    Ok(match reply.values.last() {
+        Some(damage) => Outcome::Damage(*damage),
+        None => Outcome::Fell(reply.text),
+    })
+}
+
+// Tries each candidate that compiles, in order, and returns the first that
+// gets the droid across. Otherwise returns the last one tried and how it
+// fell, or `None` if none compiled.
+pub fn search(
+    program: &[i64],
+    mode: Mode,
+    candidates: impl IntoIterator<Item = Formula>,
+) -> Result<Option<(Formula, Outcome)>, SurveyError> {
+    let mut last = None;
+    for formula in candidates {
+        let Ok(script) = compile(&formula, mode) else {
+            continue;
+        };
+        let outcome = survey(program, &script)?;
+        if let Outcome::Damage(_) = outcome {
+            return Ok(Some((formula, outcome)));
+        }
+        last = Some((formula, outcome));
+    }
+    Ok(last)
+}
+
+fn damage(s: &str, formula: &str, mode: Mode) -> i64 {
+    let program: Vec<i64> = loader::parse(s).unwrap();
+    let script = compile(&formula.parse().unwrap(), mode).unwrap();
+    match survey(&program, &script).unwrap() {
+        Outcome::Damage(damage) => damage,
+        Outcome::Fell(frame) => panic!("{}", frame),
+    }
+}
+
+// Jump if there's a hole ahead and ground to land on.
+pub fn solve_first(s: &str) -> i64 {
+    damage(s, "(!A | !B | !C) & D", Mode::Walk)
+}
+
+// As when walking, but only if the droid can step or jump again after
+// landing.
+pub fn solve_second(s: &str) -> i64 {
+    damage(s, "(!A | !B | !C) & D & (E | H)", Mode::Run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::springscript::chains;
+    use super::*;
+    use crate::intcode::lang;
+
+    // A droid that runs its script in three places, each with the ground
+    // (1) or holes (0) it sees at A to D, and whether it has to jump. It
+    // reports 19350 plus the number of instructions if the script jumps in
+    // the right places, otherwise the tiles where it fell.
+    const DROID: &str = r##"
+        var t1 = 0; var j1 = 0;
+        var t2 = 0; var j2 = 0;
+        var t3 = 0; var j3 = 0;
+
+        fn ground(place, r) {
+            if place == 1 { return r != 'A'; } // .### jump
+            if place == 2 { return r != 'C'; } // ##.# jump
+            return r != 'C' && r != 'D';        // ##.. don't
+        }
+
+        fn read(place, r) {
+            if r == 'T' {
+                if place == 1 { return t1; }
+                if place == 2 { return t2; }
+                return t3;
+            }
+            if r == 'J' {
+                if place == 1 { return j1; }
+                if place == 2 { return j2; }
+                return j3;
+            }
+            return ground(place, r);
+        }
+
+        fn write(place, r, v) {
+            if r == 'T' {
+                if place == 1 { t1 = v; }
+                if place == 2 { t2 = v; }
+                if place == 3 { t3 = v; }
+            } else {
+                if place == 1 { j1 = v; }
+                if place == 2 { j2 = v; }
+                if place == 3 { j3 = v; }
+            }
+        }
+
+        fn execute(place, op, x, y) {
+            var value = !read(place, x);
+            if op == 'A' { value = read(place, x) && read(place, y); }
+            if op == 'O' { value = read(place, x) || read(place, y); }
+            write(place, y, value);
+        }
+
+        fn skip_to(c) { while input() != c {} }
+
+        fn main() {
+            print("Input instructions:\n");
+            var n = 0;
+            var op = input();
+            while op != 'W' && op != 'R' {
+                skip_to(' ');
+                var x = input();
+                input();
+                var y = input();
+                input();
+                var place = 1;
+                while place <= 3 { execute(place, op, x, y); place = place + 1; }
+                n = n + 1;
+                op = input();
+            }
+            skip_to('\n');
+
+            var place = 1;
+            while place <= 3 {
+                if read(place, 'J') != (place != 3) {
+                    var r = 'A';
+                    while r <= 'D' {
+                        if ground(place, r) { print("#"); } else { print("."); }
+                        r = r + 1;
+                    }
+                    print("\n");
+                    return;
+                }
+                place = place + 1;
+            }
+            output(19350 + n);
+        }"##;
+
+    fn droid() -> Vec<i64> {
+        lang::compile(DROID).unwrap()
+    }
+
+    fn script(formula: &str) -> Script {
+        compile(&formula.parse().unwrap(), Mode::Walk).unwrap()
+    }
+
+    #[test]
+    fn surveys_the_hull() {
+        let droid = droid();
+        assert_eq!(
+            survey(&droid, &script("(!A | !B | !C) & D")),
+            Ok(Outcome::Damage(19356))
+        );
+        assert_eq!(
+            survey(&droid, &script("!A")),
+            Ok(Outcome::Fell("##.#\n".to_string()))
+        );
+    }
+
+    #[test]
+    fn searches_formulas() {
+        let droid = droid();
+        let candidates = ["!A", "!C & D", "A & (B | (C & D))", "!A | !C & D", "D"]
+            .map(|text| text.parse().unwrap());
+        let (formula, outcome) = search(&droid, Mode::Walk, candidates).unwrap().unwrap();
+        assert_eq!(formula.to_string(), "(!A | !C) & D");
+        assert_eq!(outcome, Outcome::Damage(19354));
+
+        let (formula, outcome) = search(&droid, Mode::Walk, chains(&['A', 'B'], 1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(formula.to_string(), "!B");
+        assert!(matches!(outcome, Outcome::Fell(_)));
+    }
+}
@@ -0,0 +1,637 @@
+// Springscript: up to 15 instructions, each `AND X Y`, `OR X Y` or `NOT X Y`,
+// that read a sensor or T or J and write T or J, followed by `WALK` or `RUN`.
+// Sensors A to D report whether there's ground one to four tiles ahead, and
+// running adds E to I for five to nine. T and J start false, and the droid
+// jumps when J ends up true.
+//
+// Formulas over the sensors, like `(!A | !B | !C) & D`, compile to
+// springscript that leaves their value in J. Binary operators apply left to
+// right without precedence.
+
+use std::fmt;
+use std::str::FromStr;
+
+pub const MAX_INSTRUCTIONS: usize = 15;
+
+const SENSORS: [char; 9] = ['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I'];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mode {
+    Walk,
+    Run,
+}
+
+impl Mode {
+    // Nearest first.
+    pub fn sensors(self) -> &'static [char] {
+        match self {
+            Mode::Walk => &SENSORS[..4],
+            Mode::Run => &SENSORS,
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Walk => write!(f, "WALK"),
+            Mode::Run => write!(f, "RUN"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Op {
+    And,
+    Or,
+    Not,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Instruction {
+    pub op: Op,
+    pub source: char,
+    pub target: char,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.op {
+            Op::And => "AND",
+            Op::Or => "OR",
+            Op::Not => "NOT",
+        };
+        write!(f, "{} {} {}", op, self.source, self.target)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ScriptError {
+    // Lines are 1-based.
+    Syntax {
+        line: usize,
+        text: String,
+    },
+    UnreadableRegister {
+        line: usize,
+        register: char,
+        mode: Mode,
+    },
+    UnwritableRegister {
+        line: usize,
+        register: char,
+    },
+    TooLong(usize),
+    MissingMode,
+    AfterMode {
+        line: usize,
+    },
+    // Compiling the formula would take a register besides T and J.
+    TooComplex,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Syntax { line, text } => {
+                write!(f, "invalid instruction on line {}: {:?}", line, text)
+            }
+            ScriptError::UnreadableRegister {
+                line,
+                register,
+                mode,
+            } => write!(f, "line {} reads {}, which {} can't", line, register, mode),
+            ScriptError::UnwritableRegister { line, register } => {
+                write!(f, "line {} writes {}, which is read-only", line, register)
+            }
+            ScriptError::TooLong(len) => write!(
+                f,
+                "{} instructions is more than the {} allowed",
+                len, MAX_INSTRUCTIONS
+            ),
+            ScriptError::MissingMode => write!(f, "script doesn't end with WALK or RUN"),
+            ScriptError::AfterMode { line } => {
+                write!(f, "line {} comes after WALK or RUN", line)
+            }
+            ScriptError::TooComplex => write!(f, "formula needs more registers than T and J"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Script {
+    pub mode: Mode,
+    pub instructions: Vec<Instruction>,
+}
+
+fn validate(
+    mode: Mode,
+    instructions: impl IntoIterator<Item = (usize, Instruction)>,
+) -> Result<Vec<Instruction>, ScriptError> {
+    let mut valid = vec![];
+    for (line, instruction) in instructions {
+        let source = instruction.source;
+        if !mode.sensors().contains(&source) && source != 'T' && source != 'J' {
+            return Err(ScriptError::UnreadableRegister {
+                line,
+                register: source,
+                mode,
+            });
+        }
+        if instruction.target != 'T' && instruction.target != 'J' {
+            return Err(ScriptError::UnwritableRegister {
+                line,
+                register: instruction.target,
+            });
+        }
+        valid.push(instruction);
+    }
+    if valid.len() > MAX_INSTRUCTIONS {
+        return Err(ScriptError::TooLong(valid.len()));
+    }
+    Ok(valid)
+}
+
+impl Script {
+    pub fn new(mode: Mode, instructions: Vec<Instruction>) -> Result<Self, ScriptError> {
+        let instructions = validate(mode, (1..).zip(instructions))?;
+        Ok(Script { mode, instructions })
+    }
+
+    // Whether the droid jumps, given which of the tiles ahead are ground.
+    // Tiles past the end of `ground` count as ground.
+    pub fn jumps(&self, ground: &[bool]) -> bool {
+        let (mut t, mut j) = (false, false);
+        for instruction in &self.instructions {
+            let source = match instruction.source {
+                'T' => t,
+                'J' => j,
+                sensor => ground
+                    .get(sensor as usize - 'A' as usize)
+                    .copied()
+                    .unwrap_or(true),
+            };
+            let target = match instruction.target {
+                'T' => &mut t,
+                _ => &mut j,
+            };
+            *target = match instruction.op {
+                Op::And => source && *target,
+                Op::Or => source || *target,
+                Op::Not => !source,
+            };
+        }
+        j
+    }
+}
+
+impl FromStr for Script {
+    type Err = ScriptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mode = None;
+        let mut instructions = vec![];
+        for (i, text) in s.lines().enumerate() {
+            let line = i + 1;
+            let words: Vec<&str> = text.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            if mode.is_some() {
+                return Err(ScriptError::AfterMode { line });
+            }
+            let syntax = || ScriptError::Syntax {
+                line,
+                text: text.to_string(),
+            };
+            let register = |word: &str| {
+                let mut chars = word.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(c),
+                    _ => Err(syntax()),
+                }
+            };
+            let op = match words[..] {
+                ["WALK"] => {
+                    mode = Some(Mode::Walk);
+                    continue;
+                }
+                ["RUN"] => {
+                    mode = Some(Mode::Run);
+                    continue;
+                }
+                ["AND", _, _] => Op::And,
+                ["OR", _, _] => Op::Or,
+                ["NOT", _, _] => Op::Not,
+                _ => return Err(syntax()),
+            };
+            let instruction = Instruction {
+                op,
+                source: register(words[1])?,
+                target: register(words[2])?,
+            };
+            instructions.push((line, instruction));
+        }
+
+        let mode = mode.ok_or(ScriptError::MissingMode)?;
+        let instructions = validate(mode, instructions)?;
+        Ok(Script { mode, instructions })
+    }
+}
+
+// The text the droid expects, ending with a newline.
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for instruction in &self.instructions {
+            writeln!(f, "{}", instruction)?;
+        }
+        writeln!(f, "{}", self.mode)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Formula {
+    Sensor(char),
+    Not(Box<Formula>),
+    And(Box<Formula>, Box<Formula>),
+    Or(Box<Formula>, Box<Formula>),
+}
+
+impl Formula {
+    // Tiles past the end of `ground` count as ground.
+    pub fn eval(&self, ground: &[bool]) -> bool {
+        match self {
+            Formula::Sensor(c) => ground
+                .get(*c as usize - 'A' as usize)
+                .copied()
+                .unwrap_or(true),
+            Formula::Not(a) => !a.eval(ground),
+            Formula::And(a, b) => a.eval(ground) && b.eval(ground),
+            Formula::Or(a, b) => a.eval(ground) || b.eval(ground),
+        }
+    }
+
+    fn is_binary(&self) -> bool {
+        matches!(self, Formula::And(..) | Formula::Or(..))
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operand = |x: &Formula| match x.is_binary() {
+            true => format!("({})", x),
+            false => x.to_string(),
+        };
+        // Formulas read left to right, but a left operand mixing the other
+        // operator is bracketed anyway so it doesn't look like precedence.
+        let left = |x: &Formula, same: bool| match same {
+            true => x.to_string(),
+            false => operand(x),
+        };
+        match self {
+            Formula::Sensor(c) => write!(f, "{}", c),
+            Formula::Not(a) => write!(f, "!{}", operand(a)),
+            Formula::And(a, b) => {
+                let same = !matches!(**a, Formula::Or(..));
+                write!(f, "{} & {}", left(a, same), operand(b))
+            }
+            Formula::Or(a, b) => {
+                let same = !matches!(**a, Formula::And(..));
+                write!(f, "{} | {}", left(a, same), operand(b))
+            }
+        }
+    }
+}
+
+// The 0-based character the formula stops making sense at.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseFormulaError {
+    pub position: usize,
+}
+
+impl fmt::Display for ParseFormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid formula at character {}", self.position)
+    }
+}
+
+impl std::error::Error for ParseFormulaError {}
+
+struct FormulaParser {
+    chars: Vec<(usize, char)>,
+    next: usize,
+    len: usize,
+}
+
+impl FormulaParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.next).map(|(_, c)| *c)
+    }
+
+    fn error<T>(&self) -> Result<T, ParseFormulaError> {
+        let position = self.chars.get(self.next).map_or(self.len, |(i, _)| *i);
+        Err(ParseFormulaError { position })
+    }
+
+    fn expr(&mut self) -> Result<Formula, ParseFormulaError> {
+        let mut left = self.unary()?;
+        while let Some(op @ ('&' | '|')) = self.peek() {
+            self.next += 1;
+            let right = Box::new(self.unary()?);
+            left = match op {
+                '&' => Formula::And(Box::new(left), right),
+                _ => Formula::Or(Box::new(left), right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Formula, ParseFormulaError> {
+        match self.peek() {
+            Some('!') => {
+                self.next += 1;
+                Ok(Formula::Not(Box::new(self.unary()?)))
+            }
+            Some('(') => {
+                self.next += 1;
+                let expr = self.expr()?;
+                if self.peek() != Some(')') {
+                    return self.error();
+                }
+                self.next += 1;
+                Ok(expr)
+            }
+            Some(c) if SENSORS.contains(&c) => {
+                self.next += 1;
+                Ok(Formula::Sensor(c))
+            }
+            _ => self.error(),
+        }
+    }
+}
+
+impl FromStr for Formula {
+    type Err = ParseFormulaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = FormulaParser {
+            chars: s
+                .char_indices()
+                .filter(|(_, c)| !c.is_whitespace())
+                .collect(),
+            next: 0,
+            len: s.len(),
+        };
+        let formula = parser.expr()?;
+        if parser.peek().is_some() {
+            return parser.error();
+        }
+        Ok(formula)
+    }
+}
+
+struct Compiler {
+    instructions: Vec<Instruction>,
+    // Registers not written yet, which are still false.
+    clean: Vec<char>,
+}
+
+impl Compiler {
+    fn emit(&mut self, op: Op, source: char, target: char) {
+        self.instructions.push(Instruction { op, source, target });
+        self.clean.retain(|r| *r != target);
+    }
+
+    // Leaves the value of `formula` in `target`, using `scratch` if it has
+    // to.
+    fn load(
+        &mut self,
+        formula: &Formula,
+        target: char,
+        scratch: Option<char>,
+    ) -> Result<(), ScriptError> {
+        match formula {
+            Formula::Sensor(c) if self.clean.contains(&target) => self.emit(Op::Or, *c, target),
+            Formula::Sensor(c) => {
+                self.emit(Op::Not, *c, target);
+                self.emit(Op::Not, target, target);
+            }
+            Formula::Not(a) => match **a {
+                Formula::Sensor(c) => self.emit(Op::Not, c, target),
+                _ => {
+                    self.load(a, target, scratch)?;
+                    self.emit(Op::Not, target, target);
+                }
+            },
+            Formula::And(a, b) | Formula::Or(a, b) => {
+                let op = match formula {
+                    Formula::And(..) => Op::And,
+                    _ => Op::Or,
+                };
+                self.load(a, target, scratch)?;
+                match **b {
+                    Formula::Sensor(c) => self.emit(op, c, target),
+                    _ => {
+                        let scratch = scratch.ok_or(ScriptError::TooComplex)?;
+                        self.load(b, scratch, None)?;
+                        self.emit(op, scratch, target);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn compile(formula: &Formula, mode: Mode) -> Result<Script, ScriptError> {
+    let mut compiler = Compiler {
+        instructions: vec![],
+        clean: vec!['T', 'J'],
+    };
+    compiler.load(formula, 'J', Some('T'))?;
+    Script::new(mode, compiler.instructions)
+}
+
+// Every formula that combines up to `len` sensors or their negations left to
+// right, shortest first.
+pub fn chains(sensors: &[char], len: usize) -> Vec<Formula> {
+    let literals: Vec<Formula> = sensors
+        .iter()
+        .flat_map(|c| {
+            let sensor = Formula::Sensor(*c);
+            [sensor.clone(), Formula::Not(Box::new(sensor))]
+        })
+        .collect();
+
+    let mut chains = vec![];
+    let mut shorter = literals.clone();
+    for _ in 0..len {
+        chains.extend(shorter.iter().cloned());
+        shorter = shorter
+            .iter()
+            .flat_map(|chain| {
+                literals.iter().flat_map(move |literal| {
+                    let (a, b) = (Box::new(chain.clone()), Box::new(literal.clone()));
+                    [Formula::And(a.clone(), b.clone()), Formula::Or(a, b)]
+                })
+            })
+            .collect();
+    }
+    chains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JUMP_GAPS: &str = "NOT A J\nNOT B T\nOR T J\nNOT C T\nOR T J\nAND D J\nWALK\n";
+
+    #[test]
+    fn parses_and_prints_scripts() {
+        let script: Script = JUMP_GAPS.parse().unwrap();
+        assert_eq!(script.mode, Mode::Walk);
+        assert_eq!(script.instructions.len(), 6);
+        assert_eq!(
+            script.instructions[1],
+            Instruction {
+                op: Op::Not,
+                source: 'B',
+                target: 'T'
+            }
+        );
+        assert_eq!(script.to_string(), JUMP_GAPS);
+
+        let script: Script = "\n  OR  E J\n\nRUN\n\n".parse().unwrap();
+        assert_eq!(script.to_string(), "OR E J\nRUN\n");
+    }
+
+    #[test]
+    fn rejects_invalid_scripts() {
+        let cases = [
+            ("NOT A J", ScriptError::MissingMode),
+            (
+                "NOT A\nWALK",
+                ScriptError::Syntax {
+                    line: 1,
+                    text: "NOT A".to_string(),
+                },
+            ),
+            (
+                "NOT A J\nXOR A J\nWALK",
+                ScriptError::Syntax {
+                    line: 2,
+                    text: "XOR A J".to_string(),
+                },
+            ),
+            (
+                "AND AB J\nWALK",
+                ScriptError::Syntax {
+                    line: 1,
+                    text: "AND AB J".to_string(),
+                },
+            ),
+            (
+                "NOT A J\n\nOR E J\nWALK",
+                ScriptError::UnreadableRegister {
+                    line: 3,
+                    register: 'E',
+                    mode: Mode::Walk,
+                },
+            ),
+            (
+                "NOT X J\nRUN",
+                ScriptError::UnreadableRegister {
+                    line: 1,
+                    register: 'X',
+                    mode: Mode::Run,
+                },
+            ),
+            (
+                "NOT A B\nRUN",
+                ScriptError::UnwritableRegister {
+                    line: 1,
+                    register: 'B',
+                },
+            ),
+            ("WALK\nNOT A J", ScriptError::AfterMode { line: 2 }),
+        ];
+        for (text, error) in cases {
+            assert_eq!(text.parse::<Script>(), Err(error), "{}", text);
+        }
+
+        let long = "NOT A J\n".repeat(16) + "WALK";
+        assert_eq!(long.parse::<Script>(), Err(ScriptError::TooLong(16)));
+        assert!(("NOT A J\n".repeat(15) + "WALK").parse::<Script>().is_ok());
+    }
+
+    #[test]
+    fn parses_and_prints_formulas() {
+        let formula: Formula = "(!A | !B | !C) & D".parse().unwrap();
+        assert_eq!(formula.to_string(), "(!A | !B | !C) & D");
+        assert_eq!(formula.to_string().parse(), Ok(formula));
+
+        let formula: Formula = "A&!(B|C)|!!D".parse().unwrap();
+        assert_eq!(formula.to_string(), "(A & !(B | C)) | !!D");
+
+        assert_eq!(
+            "A & ".parse::<Formula>(),
+            Err(ParseFormulaError { position: 4 })
+        );
+        assert_eq!(
+            "A B".parse::<Formula>(),
+            Err(ParseFormulaError { position: 2 })
+        );
+        assert_eq!(
+            "(A".parse::<Formula>(),
+            Err(ParseFormulaError { position: 2 })
+        );
+        assert_eq!(
+            "T".parse::<Formula>(),
+            Err(ParseFormulaError { position: 0 })
+        );
+    }
+
+    fn every_view(sensors: usize) -> impl Iterator<Item = Vec<bool>> {
+        (0..1 << sensors).map(move |bits| (0..sensors).map(|i| bits & (1 << i) != 0).collect())
+    }
+
+    #[test]
+    fn compiles_formulas() {
+        for (text, len) in [
+            ("D", 1),
+            ("!A", 1),
+            ("(!A | !B | !C) & D", 6),
+            ("!(A & B & C) & D", 5),
+            ("A & !(B | C) | !!D", 8),
+            ("!(!A & (B | C)) & (D | H)", 9),
+        ] {
+            let formula: Formula = text.parse().unwrap();
+            let script = compile(&formula, Mode::Run).unwrap();
+            assert_eq!(script.instructions.len(), len, "{}", script);
+            for view in every_view(9) {
+                assert_eq!(script.jumps(&view), formula.eval(&view), "{}", text);
+            }
+        }
+
+        let nested: Formula = "A & (B | (C & D))".parse().unwrap();
+        assert_eq!(compile(&nested, Mode::Walk), Err(ScriptError::TooComplex));
+        let far: Formula = "!A & E".parse().unwrap();
+        assert!(matches!(
+            compile(&far, Mode::Walk),
+            Err(ScriptError::UnreadableRegister { register: 'E', .. })
+        ));
+    }
+
+    #[test]
+    fn enumerates_chains() {
+        let chains = chains(&['A', 'B'], 2);
+        assert_eq!(chains.len(), 4 + 4 * 2 * 4);
+        assert_eq!(chains[1].to_string(), "!A");
+        assert_eq!(chains[4].to_string(), "A & A");
+        assert!(chains
+            .iter()
+            .all(|chain| compile(chain, Mode::Walk).is_ok()));
+    }
+}
@@ -1,6 +1,6 @@
 use std::convert::TryFrom;
-use std::convert::TryInto;
-use std::io::Write;
+
+use crate::intcode::{loader, symbolic, Cell, Error};
 
 #[derive(PartialEq, Debug)]
 pub enum OpCode {
@@ -22,49 +22,70 @@ impl TryFrom<usize> for OpCode {
     }
 }
 
-pub fn perform_ops(ops: &mut Vec<usize>) {
+fn read<C: Cell>(ops: &[C], address: usize, ip: usize) -> Result<C, Error<C>> {
+    ops.get(address).cloned().ok_or(Error::InvalidAddress {
+        ip,
+        address: C::from_i64(address as i64),
+    })
+}
+
+fn address<C: Cell>(ops: &[C], index: usize, ip: usize) -> Result<usize, Error<C>> {
+    let value = read(ops, index, ip)?;
+    match value.to_usize() {
+        Some(address) if address < ops.len() => Ok(address),
+        _ => Err(Error::InvalidAddress { ip, address: value }),
+    }
+}
+
+fn parse_op<C: Cell>(ops: &[C], index: usize) -> Result<OpCode, Error<C>> {
+    let value = read(ops, index, index)?;
+    value
+        .to_usize()
+        .and_then(|x| x.try_into().ok())
+        .ok_or(Error::InvalidOpCode { ip: index, value })
+}
+
+pub fn perform_ops<C: Cell>(ops: &mut [C]) -> Result<(), Error<C>> {
     let mut index = 0;
-    let mut op: OpCode = ops[index]
-        .try_into()
-        .expect(format!("Failed to unwrap {:?}", ops[index]).as_str());
+    let mut op = parse_op(ops, index)?;
 
     while op != OpCode::Halt {
-        match op {
-            OpCode::Add => {
-                let arg1 = ops[ops[index + 1]];
-                let arg2 = ops[ops[index + 2]];
-                let target_index = ops[index + 3];
-                ops[target_index] = arg1 + arg2;
-            }
-            OpCode::Mul => {
-                let arg1 = ops[ops[index + 1]];
-                let arg2 = ops[ops[index + 2]];
-                let target_index = ops[index + 3];
-                ops[target_index] = arg1 * arg2;
-            }
-            _ => panic!("Invalid opcode {:?} at index {:?}", ops[index], index),
-        }
+        let arg1 = read(ops, address(ops, index + 1, index)?, index)?;
+        let arg2 = read(ops, address(ops, index + 2, index)?, index)?;
+        let target_index = address(ops, index + 3, index)?;
+        let result = match op {
+            OpCode::Add => arg1.checked_add(&arg2),
+            OpCode::Mul => arg1.checked_mul(&arg2),
+            OpCode::Halt => unreachable!(),
+        };
+        ops[target_index] = result.ok_or(Error::Overflow { ip: index })?;
 
         index += 4;
-        op = ops[index].try_into().unwrap();
+        op = parse_op(ops, index)?;
     }
+
+    Ok(())
 }
 
-pub fn solve_first(s: &str) -> usize {
-    let mut ops: Vec<usize> = s
-        .split(",")
-        .map(|x| {
-            println!("{:?}", x);
-            std::io::stdout().flush().unwrap();
-            x.parse().unwrap()
-        })
-        .collect();
+pub fn solve_first(s: &str) -> i64 {
+    let mut ops: Vec<i64> = loader::parse(s).unwrap();
 
-    perform_ops(&mut ops);
+    perform_ops(&mut ops).unwrap();
 
     ops[0]
 }
 
+// Solves for the noun and verb symbolically instead of trying every pair.
+// `None` if no noun and verb produce 19690720.
+pub fn solve_second(s: &str) -> Option<i64> {
+    let ops: Vec<i64> = loader::parse(s).unwrap();
+
+    symbolic::explore(symbolic::symbolize(&ops, &[1, 2]), 10_000, 1_000)
+        .iter()
+        .find_map(|path| path.solve(0, 19690720, &[0..=99, 0..=99]))
+        .map(|v| 100 * v[0] + v[1])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -79,22 +100,36 @@ mod tests {
 
     #[test]
     fn solves_first_input() {
-        let path = "resources/day_two_input.txt";
-        let num = solve_first(path);
-        assert_eq!(3931283, num);
+        let mut ops: Vec<i64> = loader::load("resources/day_two_input.txt").unwrap();
+        ops[1] = 12;
+        ops[2] = 2;
+        perform_ops(&mut ops).unwrap();
+        assert_eq!(3931283, ops[0]);
+    }
+
+    #[test]
+    fn reports_overflow_with_ip() {
+        let mut ops: Vec<i64> = vec![1, 0, 0, 0, 2, 9, 9, 0, 99, i64::MAX];
+        assert_eq!(perform_ops(&mut ops), Err(Error::Overflow { ip: 4 }));
+
+        let mut ops: Vec<i128> = vec![1, 0, 0, 0, 2, 9, 9, 0, 99, i128::from(i64::MAX)];
+        perform_ops(&mut ops).unwrap();
+        assert_eq!(ops[0], i128::from(i64::MAX) * i128::from(i64::MAX));
     }
 
-    // #[test]
-    // fn solves_second_sample() {
-    //     let path = "resources/day_two_sample.txt";
-    //     let num = solve_second(path);
-    //     assert_eq!(50346, num);
-    // }
-
-    // #[test]
-    // fn solves_second_input() {
-    //     let path = "resources/day_two_input.txt";
-    //     let num = solve_second(path);
-    //     assert_eq!(5148724, num);
-    // }
+    #[test]
+    fn solves_second_input() {
+        let val = fs::read_to_string("resources/day_two_input.txt").unwrap();
+        let num = solve_second(&val).unwrap();
+        assert_eq!(6979, num);
+
+        let mut ops: Vec<i64> = loader::parse(&val).unwrap();
+        ops[1] = num / 100;
+        ops[2] = num % 100;
+        perform_ops(&mut ops).unwrap();
+        assert_eq!(19690720, ops[0]);
+
+        // ops[0] = noun + verb, which is never more than 198.
+        assert_eq!(solve_second("1101,0,0,0,99"), None);
+    }
 }
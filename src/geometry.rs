@@ -0,0 +1,145 @@
+// Points on an unbounded grid, with up as +y.
+
+use std::ops::{Add, Sub};
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, PartialOrd, Ord)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+        Direction::Left,
+    ];
+
+    pub fn turn_left(self) -> Self {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    pub fn turn_right(self) -> Self {
+        self.turn_left().turn_left().turn_left()
+    }
+
+    pub fn reverse(self) -> Self {
+        self.turn_left().turn_left()
+    }
+}
+
+impl Point {
+    pub const ORIGIN: Point = Point { x: 0, y: 0 };
+
+    pub fn new(x: i64, y: i64) -> Self {
+        Point { x, y }
+    }
+
+    pub fn offset(self, direction: Direction, distance: i64) -> Self {
+        match direction {
+            Direction::Up => Point::new(self.x, self.y + distance),
+            Direction::Right => Point::new(self.x + distance, self.y),
+            Direction::Down => Point::new(self.x, self.y - distance),
+            Direction::Left => Point::new(self.x - distance, self.y),
+        }
+    }
+
+    pub fn step(self, direction: Direction) -> Self {
+        self.offset(direction, 1)
+    }
+
+    pub fn neighbors(self) -> [Point; 4] {
+        Direction::ALL.map(|direction| self.step(direction))
+    }
+
+    // Manhattan distance from the origin.
+    pub fn distance(self) -> i64 {
+        self.x.abs() + self.y.abs()
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+// Draws the bounding box of `points` from the top row down, asking `symbol`
+// for each point in it. Empty when there are no points.
+pub fn render<'a>(
+    points: impl IntoIterator<Item = &'a Point>,
+    symbol: impl Fn(Point) -> char,
+) -> String {
+    let points: Vec<&Point> = points.into_iter().collect();
+    let (Some(left), Some(right)) = (
+        points.iter().map(|p| p.x).min(),
+        points.iter().map(|p| p.x).max(),
+    ) else {
+        return String::new();
+    };
+    let bottom = points.iter().map(|p| p.y).min().unwrap();
+    let top = points.iter().map(|p| p.y).max().unwrap();
+
+    let mut out = String::new();
+    for y in (bottom..=top).rev() {
+        out.extend((left..=right).map(|x| symbol(Point::new(x, y))));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn turns_and_steps() {
+        assert_eq!(Direction::Up.turn_right(), Direction::Right);
+        assert_eq!(Direction::Up.turn_left(), Direction::Left);
+        assert_eq!(Direction::Left.reverse(), Direction::Right);
+
+        let p = Point::new(2, -3);
+        assert_eq!(p.step(Direction::Up), Point::new(2, -2));
+        assert_eq!(p.offset(Direction::Left, 5), Point::new(-3, -3));
+        assert_eq!(p.distance(), 5);
+        assert!(p.neighbors().iter().all(|n| (*n - p).distance() == 1));
+    }
+
+    #[test]
+    fn renders_from_the_top_down() {
+        let points: HashSet<Point> = [Point::new(0, 0), Point::new(1, 1), Point::new(-1, 1)]
+            .into_iter()
+            .collect();
+        let picture = render(&points, |p| match points.contains(&p) {
+            true => '#',
+            false => '.',
+        });
+        assert_eq!(picture, "#.#\n.#.\n");
+        assert_eq!(render(&[], |_| '#'), "");
+    }
+}
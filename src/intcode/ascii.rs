@@ -0,0 +1,107 @@
+// Drives programs that talk in ASCII text, as the droids from day 17 on do.
+// Output values outside ASCII, like a final answer, are kept apart from the
+// text.
+
+use super::{Error, Machine, State};
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Reply {
+    pub text: String,
+    pub values: Vec<i64>,
+    pub halted: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ascii {
+    machine: Machine,
+}
+
+impl Ascii {
+    pub fn new(program: Vec<i64>) -> Self {
+        Self::from_machine(Machine::new(program).growable())
+    }
+
+    pub fn from_machine(machine: Machine) -> Self {
+        Ascii { machine }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    pub fn send(&mut self, text: &str) {
+        for byte in text.bytes() {
+            self.machine.push_input(i64::from(byte));
+        }
+    }
+
+    pub fn send_line(&mut self, line: &str) {
+        self.send(line);
+        self.send("\n");
+    }
+
+    // Runs until the program wants input it hasn't been sent, or halts.
+    pub fn run(&mut self) -> Result<Reply, Error<i64>> {
+        let mut reply = Reply::default();
+        loop {
+            match self.machine.run()? {
+                State::Output(value) => match u8::try_from(value) {
+                    Ok(byte) if byte.is_ascii() => reply.text.push(char::from(byte)),
+                    _ => reply.values.push(value),
+                },
+                State::NeedsInput => return Ok(reply),
+                State::Halted => {
+                    reply.halted = true;
+                    return Ok(reply);
+                }
+                State::Vetoed => unreachable!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::lang::compile;
+
+    #[test]
+    fn talks_in_lines() {
+        // Prompts with `> `, echoes a line, then reports its length.
+        let program = compile(
+            "fn main() {
+                output(62); output(32);
+                var length = 0;
+                var c = input();
+                while c != 10 { output(c); length = length + 1; c = input(); }
+                output(10);
+                output(1000 + length);
+            }",
+        )
+        .unwrap();
+        let mut ascii = Ascii::new(program);
+        assert_eq!(
+            ascii.run(),
+            Ok(Reply {
+                text: "> ".to_string(),
+                values: vec![],
+                halted: false
+            })
+        );
+        ascii.send("hel");
+        assert_eq!(ascii.run().unwrap().text, "hel");
+        ascii.send_line("lo");
+        assert_eq!(
+            ascii.run(),
+            Ok(Reply {
+                text: "lo\n".to_string(),
+                values: vec![1005],
+                halted: true
+            })
+        );
+    }
+}
@@ -0,0 +1,304 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+// Sign-magnitude integer with little-endian base 2^32 limbs. Zero is always
+// stored as an empty magnitude with `negative == false`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseBigIntError;
+
+impl fmt::Display for ParseBigIntError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid digit found in string")
+    }
+}
+
+impl std::error::Error for ParseBigIntError {}
+
+fn trim(mut magnitude: Vec<u32>) -> Vec<u32> {
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+    magnitude
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(std::cmp::max(a.len(), b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..std::cmp::max(a.len(), b.len()) {
+        let sum = u64::from(*a.get(i).unwrap_or(&0)) + u64::from(*b.get(i).unwrap_or(&0)) + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+// Requires |a| >= |b|.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, limb) in a.iter().enumerate() {
+        let mut diff = i64::from(*limb) - i64::from(*b.get(i).unwrap_or(&0)) - borrow;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        } else {
+            borrow = 0;
+        }
+        result.push(diff as u32);
+    }
+    trim(result)
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let cur = u64::from(result[i + j]) + u64::from(*x) * u64::from(*y) + carry;
+            result[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    trim(result)
+}
+
+// Divides in place by a small divisor, returning the remainder.
+fn div_rem_small(magnitude: &mut [u32], divisor: u32) -> u32 {
+    let mut rem = 0u64;
+    for limb in magnitude.iter_mut().rev() {
+        let cur = (rem << 32) | u64::from(*limb);
+        *limb = (cur / u64::from(divisor)) as u32;
+        rem = cur % u64::from(divisor);
+    }
+    rem as u32
+}
+
+impl BigInt {
+    fn from_parts(negative: bool, magnitude: Vec<u32>) -> BigInt {
+        let magnitude = trim(magnitude);
+        BigInt {
+            negative: negative && !magnitude.is_empty(),
+            magnitude,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let mut value = 0u64;
+        for limb in self.magnitude.iter().rev() {
+            value = (value << 32) | u64::from(*limb);
+        }
+        if self.negative {
+            if value <= i64::MAX as u64 + 1 {
+                Some((value as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else {
+            i64::try_from(value).ok()
+        }
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        let abs = value.unsigned_abs();
+        BigInt::from_parts(value < 0, vec![abs as u32, (abs >> 32) as u32])
+    }
+}
+
+impl std::ops::Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(
+                self.negative,
+                add_magnitude(&self.magnitude, &other.magnitude),
+            );
+        }
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::from_parts(
+                other.negative,
+                sub_magnitude(&other.magnitude, &self.magnitude),
+            ),
+            _ => BigInt::from_parts(
+                self.negative,
+                sub_magnitude(&self.magnitude, &other.magnitude),
+            ),
+        }
+    }
+}
+
+impl std::ops::Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_parts(
+            self.negative != other.negative,
+            mul_magnitude(&self.magnitude, &other.magnitude),
+        )
+    }
+}
+
+impl std::ops::Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        let mut magnitude = self.magnitude.clone();
+        let mut chunks = vec![];
+        while !magnitude.is_empty() {
+            chunks.push(div_rem_small(&mut magnitude, 1_000_000_000));
+            magnitude = trim(magnitude);
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() {
+            return Err(ParseBigIntError);
+        }
+        let mut magnitude: Vec<u32> = vec![];
+        for c in digits.chars() {
+            let digit = c.to_digit(10).ok_or(ParseBigIntError)?;
+            let mut carry = u64::from(digit);
+            for limb in magnitude.iter_mut() {
+                let cur = u64::from(*limb) * 10 + carry;
+                *limb = cur as u32;
+                carry = cur >> 32;
+            }
+            if carry > 0 {
+                magnitude.push(carry as u32);
+            }
+        }
+        Ok(BigInt::from_parts(negative, magnitude))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_strings() {
+        for s in [
+            "0",
+            "1",
+            "-1",
+            "4294967296",
+            "-18446744073709551617",
+            "1125899906842624",
+        ] {
+            assert_eq!(big(s).to_string(), s);
+        }
+        assert_eq!(big("-0"), BigInt::from(0));
+        assert!("12a".parse::<BigInt>().is_err());
+    }
+
+    #[test]
+    fn adds_with_mixed_signs() {
+        assert_eq!(&big("4294967295") + &big("1"), big("4294967296"));
+        assert_eq!(&big("-5") + &big("3"), big("-2"));
+        assert_eq!(&big("5") + &big("-5"), big("0"));
+        assert_eq!(&big("-4294967296") + &big("-1"), big("-4294967297"));
+    }
+
+    #[test]
+    fn multiplies_past_i128() {
+        let x = big("170141183460469231731687303715884105727");
+        assert_eq!(
+            (&x * &x).to_string(),
+            "28948022309329048855892746252171976962977213799489202546401021394546514198529"
+        );
+        assert_eq!(&big("-3") * &big("7"), big("-21"));
+    }
+
+    #[test]
+    fn converts_to_i64() {
+        assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(BigInt::from(i64::MAX).to_i64(), Some(i64::MAX));
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+    }
+
+    #[test]
+    fn orders_by_value() {
+        assert!(big("-10") < big("-2"));
+        assert!(big("-2") < big("0"));
+        assert!(big("4294967296") > big("4294967295"));
+    }
+}
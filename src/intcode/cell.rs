@@ -0,0 +1,106 @@
+use std::fmt::{Debug, Display};
+
+use super::BigInt;
+
+pub trait Cell: Clone + Debug + Display + PartialEq + PartialOrd {
+    fn from_i64(value: i64) -> Self;
+    fn to_i64(&self) -> Option<i64>;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+    fn zero() -> Self {
+        Self::from_i64(0)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == Self::zero()
+    }
+
+    fn to_usize(&self) -> Option<usize> {
+        self.to_i64().and_then(|x| usize::try_from(x).ok())
+    }
+}
+
+impl Cell for i64 {
+    fn from_i64(value: i64) -> Self {
+        value
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        i64::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        i64::checked_mul(*self, *other)
+    }
+}
+
+impl Cell for i128 {
+    fn from_i64(value: i64) -> Self {
+        i128::from(value)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        i64::try_from(*self).ok()
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        i128::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        i128::checked_mul(*self, *other)
+    }
+}
+
+impl Cell for BigInt {
+    fn from_i64(value: i64) -> Self {
+        BigInt::from(value)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        BigInt::to_i64(self)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn is_zero(&self) -> bool {
+        BigInt::is_zero(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn i64_reports_overflow() {
+        assert_eq!(Cell::checked_mul(&i64::MAX, &2), None);
+        assert_eq!(Cell::checked_add(&i64::MAX, &1), None);
+        assert_eq!(Cell::checked_add(&1i64, &2), Some(3));
+    }
+
+    #[test]
+    fn i128_holds_i64_products() {
+        let x = i128::from(i64::MAX);
+        assert_eq!(Cell::checked_mul(&x, &x), Some(x * x));
+        assert_eq!((x * x).to_i64(), None);
+    }
+
+    #[test]
+    fn negative_cells_are_not_addresses() {
+        assert_eq!((-1i64).to_usize(), None);
+        assert_eq!(BigInt::from(-1).to_usize(), None);
+        assert_eq!(BigInt::from(7).to_usize(), Some(7));
+    }
+}
@@ -0,0 +1,176 @@
+// The published day two, five and nine examples, run through every
+// interpreter that supports their opcodes in both lenient and strict mode.
+
+use super::{loader, Error};
+use crate::{day_five, day_two};
+
+const DAY_TWO: [(&[i64], &[i64]); 5] = [
+    (&[1, 0, 0, 0, 99], &[2, 0, 0, 0, 99]),
+    (&[2, 3, 0, 3, 99], &[2, 3, 0, 6, 99]),
+    (&[2, 4, 4, 5, 99, 0], &[2, 4, 4, 5, 99, 9801]),
+    (
+        &[1, 1, 1, 4, 99, 5, 6, 0, 99],
+        &[30, 1, 1, 4, 2, 5, 6, 0, 99],
+    ),
+    (
+        &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+        &[3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50],
+    ),
+];
+
+const EQUAL_TO_EIGHT_POSITION: &[i64] = &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+const LESS_THAN_EIGHT_POSITION: &[i64] = &[3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
+const EQUAL_TO_EIGHT_IMMEDIATE: &[i64] = &[3, 3, 1108, -1, 8, 3, 4, 3, 99];
+const LESS_THAN_EIGHT_IMMEDIATE: &[i64] = &[3, 3, 1107, -1, 8, 3, 4, 3, 99];
+const JUMP_POSITION: &[i64] = &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+const JUMP_IMMEDIATE: &[i64] = &[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+const COMPARE_TO_EIGHT: &[i64] = &[
+    3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+    1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105,
+    1, 46, 98, 99,
+];
+
+// Outputs itself. It also uses the cells just past its end, where memory
+// starts out zero.
+const QUINE: [i64; 16] = [
+    109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+];
+
+type Run = fn(&mut [i64], i64) -> Result<Vec<i64>, Error<i64>>;
+
+const RUNS: [(&str, Run); 2] = [
+    ("lenient", day_five::run_ops),
+    ("strict", day_five::run_ops_strict),
+];
+
+fn outputs(program: &[i64], input: i64) -> Vec<Vec<i64>> {
+    RUNS.iter()
+        .map(|(name, run)| {
+            let mut ops = program.to_vec();
+            run(&mut ops, input).unwrap_or_else(|e| panic!("{} run failed: {}", name, e))
+        })
+        .collect()
+}
+
+#[test]
+fn day_two_examples() {
+    for (program, expected) in DAY_TWO {
+        let mut ops = program.to_vec();
+        day_two::perform_ops(&mut ops).unwrap();
+        assert_eq!(ops, expected);
+
+        for (name, run) in RUNS {
+            let mut ops = program.to_vec();
+            assert_eq!(run(&mut ops, 0), Ok(vec![]), "{}", name);
+            assert_eq!(ops, expected, "{}", name);
+        }
+    }
+}
+
+#[test]
+fn echo() {
+    assert_eq!(outputs(&[3, 0, 4, 0, 99], 42), vec![vec![42]; 2]);
+}
+
+#[test]
+fn parameter_modes() {
+    for program in [&[1002, 4, 3, 4, 33][..], &[1101, 100, -1, 4, 0]] {
+        for (name, run) in RUNS {
+            let mut ops = program.to_vec();
+            run(&mut ops, 0).unwrap();
+            assert_eq!(ops[4], 99, "{}", name);
+        }
+    }
+}
+
+#[test]
+fn comparisons() {
+    for input in [7, 8, 9] {
+        let equal = vec![vec![(input == 8) as i64]; 2];
+        let less = vec![vec![(input < 8) as i64]; 2];
+        assert_eq!(outputs(EQUAL_TO_EIGHT_POSITION, input), equal);
+        assert_eq!(outputs(LESS_THAN_EIGHT_POSITION, input), less);
+        assert_eq!(outputs(EQUAL_TO_EIGHT_IMMEDIATE, input), equal);
+        assert_eq!(outputs(LESS_THAN_EIGHT_IMMEDIATE, input), less);
+    }
+}
+
+#[test]
+fn jumps() {
+    for (input, expected) in [(0, 0), (5, 1), (-3, 1)] {
+        assert_eq!(outputs(JUMP_POSITION, input), vec![vec![expected]; 2]);
+        assert_eq!(outputs(JUMP_IMMEDIATE, input), vec![vec![expected]; 2]);
+    }
+}
+
+#[test]
+fn compare_to_eight() {
+    for (input, expected) in [(7, 999), (8, 1000), (9, 1001)] {
+        assert_eq!(outputs(COMPARE_TO_EIGHT, input), vec![vec![expected]; 2]);
+    }
+}
+
+#[test]
+fn output_respects_mode() {
+    assert_eq!(outputs(&[104, 7, 4, 0, 99], 0), vec![vec![7, 104]; 2]);
+}
+
+#[test]
+fn relative_base() {
+    let mut quine = QUINE.to_vec();
+    quine.resize(102, 0);
+    assert_eq!(outputs(&quine, 0), vec![QUINE.to_vec(); 2]);
+}
+
+#[test]
+fn large_numbers() {
+    let sixteen_digits = &[1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+    assert_eq!(outputs(sixteen_digits, 0), vec![vec![1219070632396864]; 2]);
+    let large = &[104, 1125899906842624, 99];
+    assert_eq!(outputs(large, 0), vec![vec![1125899906842624]; 2]);
+}
+
+#[test]
+fn strict_rejects_immediate_writes() {
+    for program in [
+        &[11101i64, 1, 1, 0, 99][..],
+        &[103, 0, 99],
+        &[11108, 1, 1, 0, 99],
+    ] {
+        let mut ops = program.to_vec();
+        assert_eq!(
+            day_five::run_ops_strict(&mut ops, 0),
+            Err(Error::ImmediateWrite { ip: 0 })
+        );
+    }
+}
+
+#[test]
+fn strict_rejects_junk_digits() {
+    for program in [
+        &[100001i64, 0, 0, 0, 99][..],
+        &[1099],
+        &[1104, 5, 99],
+        &[11105, 1, 3, 99],
+    ] {
+        let mut ops = program.to_vec();
+        assert_eq!(
+            day_five::run_ops_strict(&mut ops, 0),
+            Err(Error::InvalidOpCode {
+                ip: 0,
+                value: program[0]
+            })
+        );
+        let mut ops = program.to_vec();
+        assert!(day_five::run_ops(&mut ops, 0).is_ok());
+    }
+}
+
+#[test]
+fn day_five_input_is_strictly_valid() {
+    let program: Vec<i64> = loader::load("resources/day_five_input.txt").unwrap();
+    for input in [1, 5] {
+        let results = outputs(&program, input);
+        assert_eq!(results[0], results[1]);
+    }
+}
@@ -0,0 +1,616 @@
+// Decompiles Intcode programs to C-like pseudo-code. Code is found by following
+// control flow from address 0, so data mixed in with instructions is left
+// alone. Recognized idioms:
+//
+// - `if`/`else` from forward conditional jumps, with a comparison folded into
+//   the condition when its result is only stored to be jumped on
+// - `while` loops whose body ends by jumping back to the condition, and `for`
+//   loops when the body also ends by incrementing the compared variable
+// - calls: storing the return address, then jumping to the function; and
+//   returns: an unconditional jump through a relative-mode cell
+// - variables for position-mode cells outside the code
+//
+// Anything else is emitted as `goto`s, and unreachable or undecodable cells
+// that control flow runs into are emitted raw.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::day_five::{decode, Mode, OpCode};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operand {
+    Imm(i64),
+    Pos(i64),
+    Rel(i64),
+}
+
+#[derive(Clone, Debug)]
+struct Instr {
+    addr: usize,
+    op: OpCode,
+    params: Vec<Operand>,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Instr(Instr),
+    Raw(usize, i64),
+}
+
+impl Node {
+    fn addr(&self) -> usize {
+        match self {
+            Node::Instr(instr) => instr.addr,
+            Node::Raw(addr, _) => *addr,
+        }
+    }
+
+    fn instr(&self) -> Option<&Instr> {
+        match self {
+            Node::Instr(instr) => Some(instr),
+            Node::Raw(..) => None,
+        }
+    }
+}
+
+impl Instr {
+    fn next(&self) -> usize {
+        self.addr + 1 + self.params.len()
+    }
+
+    fn is_jump(&self) -> bool {
+        matches!(self.op, OpCode::JumpIfTrue | OpCode::JumpIfFalse)
+    }
+
+    fn is_unconditional(&self) -> bool {
+        match (self.op, self.params.first()) {
+            (OpCode::JumpIfTrue, Some(Operand::Imm(x))) => *x != 0,
+            (OpCode::JumpIfFalse, Some(Operand::Imm(x))) => *x == 0,
+            _ => false,
+        }
+    }
+
+    fn is_conditional(&self) -> bool {
+        self.is_jump() && !self.is_unconditional()
+    }
+
+    fn target(&self) -> Option<usize> {
+        match (self.is_jump(), self.params.get(1)) {
+            (true, Some(Operand::Imm(x))) => usize::try_from(*x).ok(),
+            _ => None,
+        }
+    }
+
+    fn is_return(&self) -> bool {
+        self.is_unconditional() && matches!(self.params[1], Operand::Rel(_))
+    }
+
+    fn stored_constant(&self) -> Option<i64> {
+        match (self.op, self.params.first(), self.params.get(1)) {
+            (OpCode::Add, Some(Operand::Imm(a)), Some(Operand::Imm(b))) => a.checked_add(*b),
+            (OpCode::Mul, Some(Operand::Imm(a)), Some(Operand::Imm(b))) => a.checked_mul(*b),
+            _ => None,
+        }
+    }
+}
+
+fn decode_at(ops: &[i64], addr: usize) -> Option<Instr> {
+    let (mode3, mode2, mode1, op) = usize::try_from(*ops.get(addr)?).ok().and_then(decode)?;
+    let modes = [mode1, mode2, mode3];
+    let params = (0..op.params())
+        .map(|i| {
+            let value = *ops.get(addr + 1 + i)?;
+            Some(match modes[i] {
+                Mode::Position => Operand::Pos(value),
+                Mode::Immediate => Operand::Imm(value),
+                Mode::Relative => Operand::Rel(value),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Instr { addr, op, params })
+}
+
+// A call stores the address following the jump, then jumps unconditionally.
+fn is_call(ops: &[i64], jump: &Instr) -> bool {
+    jump.is_unconditional()
+        && jump.target().is_some()
+        && jump.addr >= 4
+        && decode_at(ops, jump.addr - 4).is_some_and(|prev| {
+            prev.next() == jump.addr && prev.stored_constant() == Some(jump.next() as i64)
+        })
+}
+
+// Everything reachable from the entry without entering called functions.
+fn discover(ops: &[i64], entry: usize, calls: &mut BTreeSet<usize>) -> Vec<Node> {
+    let mut nodes = BTreeMap::new();
+    let mut pending = vec![entry];
+    while let Some(addr) = pending.pop() {
+        if nodes.contains_key(&addr) || addr >= ops.len() {
+            continue;
+        }
+        let instr = match decode_at(ops, addr) {
+            Some(instr) => instr,
+            None => {
+                nodes.insert(addr, Node::Raw(addr, ops[addr]));
+                continue;
+            }
+        };
+        match instr.op {
+            OpCode::Halt => {}
+            _ if is_call(ops, &instr) => {
+                calls.insert(instr.target().unwrap());
+                pending.push(instr.next());
+            }
+            _ if instr.is_unconditional() => pending.extend(instr.target()),
+            _ if instr.is_jump() => {
+                pending.push(instr.next());
+                pending.extend(instr.target());
+            }
+            _ => pending.push(instr.next()),
+        }
+        nodes.insert(addr, Node::Instr(instr));
+    }
+    nodes.into_values().collect()
+}
+
+enum Stmt {
+    Line(usize, String),
+    If(usize, String, Vec<Stmt>, Vec<Stmt>),
+    While(usize, String, Vec<Stmt>),
+    For(usize, String, String, Vec<Stmt>),
+}
+
+impl Stmt {
+    fn addr(&self) -> usize {
+        match self {
+            Stmt::Line(addr, _)
+            | Stmt::If(addr, ..)
+            | Stmt::While(addr, ..)
+            | Stmt::For(addr, ..) => *addr,
+        }
+    }
+}
+
+// A branch condition as written when it holds, and when it doesn't.
+struct Cond {
+    holds: String,
+    fails: String,
+}
+
+struct Decompiler<'a> {
+    ops: &'a [i64],
+    code: BTreeSet<usize>,
+    jump_targets: BTreeSet<usize>,
+    labels: BTreeSet<usize>,
+}
+
+impl Decompiler<'_> {
+    fn operand(&self, operand: Operand) -> String {
+        match operand {
+            Operand::Imm(x) => x.to_string(),
+            Operand::Pos(a) if a >= 0 && self.code.contains(&(a as usize)) => {
+                format!("mem[{}]", a)
+            }
+            Operand::Pos(a) => format!("v{}", a),
+            Operand::Rel(k) => format!("rb[{}]", k),
+        }
+    }
+
+    fn line(&self, instr: &Instr) -> String {
+        let p = |i: usize| self.operand(instr.params[i]);
+        match instr.op {
+            OpCode::Add if instr.params[1] == Operand::Imm(0) => format!("{} = {};", p(2), p(0)),
+            OpCode::Add if instr.params[0] == Operand::Imm(0) => format!("{} = {};", p(2), p(1)),
+            OpCode::Add => format!("{} = {} + {};", p(2), p(0), p(1)),
+            OpCode::Mul if instr.params[1] == Operand::Imm(1) => format!("{} = {};", p(2), p(0)),
+            OpCode::Mul => format!("{} = {} * {};", p(2), p(0), p(1)),
+            OpCode::LessThan => format!("{} = {} < {};", p(2), p(0), p(1)),
+            OpCode::Equals => format!("{} = {} == {};", p(2), p(0), p(1)),
+            OpCode::Input => format!("{} = input();", p(0)),
+            OpCode::Out => format!("output({});", p(0)),
+            OpCode::AdjustRelativeBase => format!("rb += {};", p(0)),
+            OpCode::Halt => "halt();".to_string(),
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let goto = match instr.target() {
+                    _ if instr.is_return() => return "return;".to_string(),
+                    Some(target) => format!("goto L{};", target),
+                    None => format!("goto *{};", p(1)),
+                };
+                if instr.is_unconditional() {
+                    goto
+                } else {
+                    let cond = self.plain_cond(instr);
+                    let jumps = if instr.op == OpCode::JumpIfTrue {
+                        cond.holds
+                    } else {
+                        cond.fails
+                    };
+                    format!("if ({}) {}", jumps, goto)
+                }
+            }
+        }
+    }
+
+    fn plain_cond(&self, jump: &Instr) -> Cond {
+        let x = self.operand(jump.params[0]);
+        Cond {
+            fails: format!("!{}", x),
+            holds: x,
+        }
+    }
+
+    // A comparison immediately consumed by the conditional jump after it.
+    fn compare_cond(&self, compare: &Instr, jump: &Instr) -> Option<Cond> {
+        let (holds, fails) = match compare.op {
+            OpCode::LessThan => ("<", ">="),
+            OpCode::Equals => ("==", "!="),
+            _ => return None,
+        };
+        let stored = match compare.params[2] {
+            Operand::Pos(a) => Operand::Pos(a),
+            Operand::Rel(k) => Operand::Rel(k),
+            Operand::Imm(_) => return None,
+        };
+        if compare.next() != jump.addr
+            || jump.params[0] != stored
+            || self.jump_targets.contains(&jump.addr)
+        {
+            return None;
+        }
+        let (a, b) = (
+            self.operand(compare.params[0]),
+            self.operand(compare.params[1]),
+        );
+        Some(Cond {
+            holds: format!("{} {} {}", a, holds, b),
+            fails: format!("{} {} {}", a, fails, b),
+        })
+    }
+
+    fn block(&mut self, nodes: &[Node], from: usize, to: usize) -> Vec<Stmt> {
+        let find = |addr: usize| -> Option<usize> {
+            if to < nodes.len() && nodes[to].addr() == addr {
+                return Some(to);
+            }
+            if to == nodes.len() && nodes.last()?.instr().is_some_and(|i| i.next() == addr) {
+                return Some(to);
+            }
+            nodes[from..to]
+                .iter()
+                .position(|n| n.addr() == addr)
+                .map(|i| i + from)
+        };
+
+        let mut stmts = vec![];
+        let mut i = from;
+        while i < to {
+            let instr = match &nodes[i] {
+                Node::Instr(instr) => instr,
+                Node::Raw(addr, value) => {
+                    stmts.push(Stmt::Line(*addr, format!("/* {}: {} */", addr, value)));
+                    i += 1;
+                    continue;
+                }
+            };
+
+            // Conditional jump, possibly with the comparison feeding it.
+            let next_jump = nodes.get(i + 1).and_then(|n| n.instr());
+            let (j, cond, compare) = match next_jump {
+                Some(jump) if i + 1 < to && jump.is_conditional() => {
+                    match self.compare_cond(instr, jump) {
+                        Some(cond) => (i + 1, Some(cond), Some(instr)),
+                        None => (i, None, None),
+                    }
+                }
+                _ => (i, None, None),
+            };
+            let jump = nodes[j].instr().unwrap();
+            if jump.is_conditional() {
+                let cond = cond.unwrap_or_else(|| self.plain_cond(jump));
+                let body_runs = if jump.op == OpCode::JumpIfFalse {
+                    cond.holds
+                } else {
+                    cond.fails
+                };
+                let end = jump.target().and_then(find).filter(|end| *end > j);
+                if let Some(end) = end {
+                    let last = nodes[end - 1].instr().filter(|last| {
+                        end - 1 > j && last.is_unconditional() && !is_call(self.ops, last)
+                    });
+                    let back = last.and_then(|l| l.target());
+
+                    if back == Some(instr.addr) {
+                        let step = compare.and_then(|c| self.step(c, &nodes[j + 1..end - 1]));
+                        match step {
+                            Some(step) => {
+                                let body = self.block(nodes, j + 1, end - 2);
+                                stmts.push(Stmt::For(instr.addr, body_runs, step, body));
+                            }
+                            None => {
+                                let body = self.block(nodes, j + 1, end - 1);
+                                stmts.push(Stmt::While(instr.addr, body_runs, body));
+                            }
+                        }
+                        i = end;
+                        continue;
+                    }
+
+                    let rest = back.and_then(find).filter(|rest| *rest > end);
+                    if let Some(rest) = rest {
+                        let then = self.block(nodes, j + 1, end - 1);
+                        let otherwise = self.block(nodes, end, rest);
+                        stmts.push(Stmt::If(instr.addr, body_runs, then, otherwise));
+                        i = rest;
+                        continue;
+                    }
+
+                    let then = self.block(nodes, j + 1, end);
+                    stmts.push(Stmt::If(instr.addr, body_runs, then, vec![]));
+                    i = end;
+                    continue;
+                }
+            }
+
+            if let Some(jump) = next_jump.filter(|j| i + 1 < to && is_call(self.ops, j)) {
+                let target = jump.target().unwrap();
+                stmts.push(Stmt::Line(
+                    instr.addr,
+                    format!("{}();", function_name(target)),
+                ));
+                i += 2;
+                continue;
+            }
+
+            if let (Some(target), false) = (instr.target(), instr.is_return()) {
+                self.labels.insert(target);
+            }
+            stmts.push(Stmt::Line(instr.addr, self.line(instr)));
+            i += 1;
+        }
+        stmts
+    }
+
+    // `v += k` when a loop body ends by adding a constant to the compared
+    // variable.
+    fn step(&self, compare: &Instr, body: &[Node]) -> Option<String> {
+        let last = body.last()?.instr()?;
+        let var = compare.params[0];
+        if last.op != OpCode::Add || last.params[2] != var || matches!(var, Operand::Imm(_)) {
+            return None;
+        }
+        let k = match (last.params[0], last.params[1]) {
+            (a, Operand::Imm(k)) if a == var => k,
+            (Operand::Imm(k), b) if b == var => k,
+            _ => return None,
+        };
+        Some(format!("{} += {}", self.operand(var), k))
+    }
+
+    fn render(&self, stmts: &[Stmt], depth: usize, out: &mut String) {
+        let indent = "    ".repeat(depth);
+        for stmt in stmts {
+            if self.labels.contains(&stmt.addr()) {
+                out.push_str(&format!("{}L{}:\n", "    ".repeat(depth - 1), stmt.addr()));
+            }
+            match stmt {
+                Stmt::Line(_, line) => out.push_str(&format!("{}{}\n", indent, line)),
+                Stmt::If(_, cond, then, otherwise) => {
+                    out.push_str(&format!("{}if ({}) {{\n", indent, cond));
+                    self.render(then, depth + 1, out);
+                    if !otherwise.is_empty() {
+                        out.push_str(&format!("{}}} else {{\n", indent));
+                        self.render(otherwise, depth + 1, out);
+                    }
+                    out.push_str(&format!("{}}}\n", indent));
+                }
+                Stmt::While(_, cond, body) => {
+                    out.push_str(&format!("{}while ({}) {{\n", indent, cond));
+                    self.render(body, depth + 1, out);
+                    out.push_str(&format!("{}}}\n", indent));
+                }
+                Stmt::For(_, cond, step, body) => {
+                    out.push_str(&format!("{}for (; {}; {}) {{\n", indent, cond, step));
+                    self.render(body, depth + 1, out);
+                    out.push_str(&format!("{}}}\n", indent));
+                }
+            }
+        }
+    }
+}
+
+fn function_name(entry: usize) -> String {
+    if entry == 0 {
+        "main".to_string()
+    } else {
+        format!("f{}", entry)
+    }
+}
+
+pub fn decompile(ops: &[i64]) -> String {
+    let mut functions = BTreeMap::new();
+    let mut calls = BTreeSet::from([0]);
+    while let Some(entry) = calls.iter().find(|e| !functions.contains_key(*e)).copied() {
+        let nodes = discover(ops, entry, &mut calls);
+        functions.insert(entry, nodes);
+    }
+
+    let instrs = || functions.values().flatten().filter_map(|n| n.instr());
+    let mut decompiler = Decompiler {
+        ops,
+        code: functions
+            .values()
+            .flatten()
+            .flat_map(|n| match n {
+                Node::Instr(i) => i.addr..i.next(),
+                Node::Raw(addr, _) => *addr..addr + 1,
+            })
+            .collect(),
+        jump_targets: instrs().filter_map(|i| i.target()).collect(),
+        labels: BTreeSet::new(),
+    };
+
+    let mut out = String::new();
+    let variables: BTreeSet<i64> = instrs()
+        .flat_map(|i| i.params.iter())
+        .filter_map(|p| match p {
+            Operand::Pos(a) if *a >= 0 && !decompiler.code.contains(&(*a as usize)) => Some(*a),
+            _ => None,
+        })
+        .collect();
+    for a in &variables {
+        let value = ops.get(*a as usize).copied().unwrap_or(0);
+        out.push_str(&format!("int v{} = {};\n", a, value));
+    }
+
+    let bodies: Vec<(usize, Vec<Stmt>)> = functions
+        .iter()
+        .map(|(entry, nodes)| (*entry, decompiler.block(nodes, 0, nodes.len())))
+        .collect();
+    for (entry, body) in bodies {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("void {}() {{\n", function_name(entry)));
+        decompiler.render(&body, 1, &mut out);
+        out.push_str("}\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::loader;
+
+    #[test]
+    fn recognizes_counting_loops() {
+        let ops = vec![
+            1101, 0, 0, 21, 1007, 21, 5, 22, 1006, 22, 20, 4, 21, 1001, 21, 1, 21, 1105, 1, 4, 99,
+            0, 0,
+        ];
+        assert_eq!(
+            decompile(&ops),
+            "int v21 = 0;
+int v22 = 0;
+
+void main() {
+    v21 = 0;
+    for (; v21 < 5; v21 += 1) {
+        output(v21);
+    }
+    halt();
+}
+"
+        );
+    }
+
+    #[test]
+    fn recognizes_if_else() {
+        let mut ops = vec![
+            3, 30, 1008, 30, 0, 31, 1005, 31, 14, 104, 1, 1106, 0, 16, 104, 2, 99,
+        ];
+        ops.resize(32, 0);
+        assert_eq!(
+            decompile(&ops),
+            "int v30 = 0;
+int v31 = 0;
+
+void main() {
+    v30 = input();
+    if (v30 != 0) {
+        output(1);
+    } else {
+        output(2);
+    }
+    halt();
+}
+"
+        );
+    }
+
+    #[test]
+    fn recognizes_calls_and_returns() {
+        let ops = vec![
+            109, 50, 21101, 9, 0, 0, 1105, 1, 10, 99, 109, 1, 104, 7, 109, -1, 2105, 1, 0,
+        ];
+        assert_eq!(
+            decompile(&ops),
+            "void main() {
+    rb += 50;
+    f10();
+    halt();
+}
+
+void f10() {
+    rb += 1;
+    output(7);
+    rb += -1;
+    return;
+}
+"
+        );
+    }
+
+    #[test]
+    fn degrades_to_gotos_and_raw_cells() {
+        let mut ops = vec![1001, 20, -1, 20, 1005, 20, 0, 1105, 1, 10, 12345];
+        ops.resize(20, 0);
+        ops.push(3);
+        assert_eq!(
+            decompile(&ops),
+            "int v20 = 3;
+
+void main() {
+L0:
+    v20 = v20 + -1;
+    if (v20) goto L0;
+    goto L10;
+L10:
+    /* 10: 12345 */
+}
+"
+        );
+    }
+
+    #[test]
+    fn walks_data_through_the_relative_base() {
+        // Outputs "Hi\n" a cell at a time, then how many cells it output.
+        let ops = vec![
+            109, 16, 204, 0, 109, 1, 1001, 20, 1, 20, 1205, 0, 2, 4, 20, 99, 72, 105, 10, 0, 0,
+        ];
+        assert_eq!(
+            decompile(&ops),
+            "int v20 = 0;
+
+void main() {
+    rb += 16;
+L2:
+    output(rb[0]);
+    rb += 1;
+    v20 = v20 + 1;
+    if (rb[0]) goto L2;
+    output(v20);
+    halt();
+}
+"
+        );
+    }
+
+    #[test]
+    fn shows_self_modifying_code() {
+        // The day five program patches its own next instruction before
+        // running it, so only the patch itself can be decompiled.
+        let ops: Vec<i64> = loader::load("resources/day_five_input.txt").unwrap();
+        assert_eq!(
+            decompile(&ops),
+            "int v225 = 0;
+
+void main() {
+    v225 = input();
+    mem[6] = v225 + mem[6];
+    /* 6: 1100 */
+}
+"
+        );
+    }
+}
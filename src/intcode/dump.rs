@@ -0,0 +1,251 @@
+// Hexdump-style views of Intcode memory, for reading a program or what a run
+// did to it. `dump` shows every row of cells with hints for the instructions
+// that start in it:
+//
+//      0:  1  9 10  3  2  3 11  0  0: add [9] [10] [3]  4: mul [3] [11] [0]
+//      8: 99 30 40 50              8: halt
+//
+// and `diff` shows only the rows that changed, marking the changed cells:
+//
+//     - 0:    1    9   10    3    2    3   11    0
+//     + 0: 3500    9   10   70    2    3   11    0
+//          ^^^^           ^^^^
+//
+// Hints come from decoding instructions one after another from address 0, so
+// data that happens to decode is hinted too.
+
+use super::Cell;
+use crate::day_five::{decode, Mode, OpCode};
+
+// Cells per row.
+pub const ROW: usize = 8;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Change<C> {
+    pub address: usize,
+    // `None` where one side is shorter than the other.
+    pub before: Option<C>,
+    pub after: Option<C>,
+}
+
+fn mnemonic(op: OpCode) -> &'static str {
+    match op {
+        OpCode::Add => "add",
+        OpCode::Mul => "mul",
+        OpCode::Input => "in",
+        OpCode::Out => "out",
+        OpCode::JumpIfTrue => "jt",
+        OpCode::JumpIfFalse => "jf",
+        OpCode::LessThan => "lt",
+        OpCode::Equals => "eq",
+        OpCode::AdjustRelativeBase => "arb",
+        OpCode::Halt => "halt",
+    }
+}
+
+// The instruction at `address` and its length, if it decodes and fits.
+fn hint_at<C: Cell>(memory: &[C], address: usize) -> Option<(String, usize)> {
+    let value = memory[address].to_usize().filter(|x| *x < 100_000)?;
+    let (mode3, mode2, mode1, op) = decode(value)?;
+    let modes = [mode1, mode2, mode3];
+    let mut text = mnemonic(op).to_string();
+    for (i, mode) in modes.iter().enumerate().take(op.params()) {
+        if op.write_param() == Some(i + 1) && *mode == Mode::Immediate {
+            return None;
+        }
+        let param = memory.get(address + 1 + i)?;
+        text += &match mode {
+            Mode::Position => format!(" [{}]", param),
+            Mode::Immediate => format!(" {}", param),
+            Mode::Relative => format!(" rb[{}]", param),
+        };
+    }
+    Some((text, 1 + op.params()))
+}
+
+fn hints<C: Cell>(memory: &[C]) -> Vec<(usize, String)> {
+    let mut hints = vec![];
+    let mut address = 0;
+    while address < memory.len() {
+        match hint_at(memory, address) {
+            Some((text, len)) => {
+                hints.push((address, text));
+                address += len;
+            }
+            None => address += 1,
+        }
+    }
+    hints
+}
+
+fn digits(n: usize) -> usize {
+    n.to_string().len()
+}
+
+fn width<'a, C: Cell + 'a>(cells: impl Iterator<Item = &'a C>) -> usize {
+    cells.map(|x| x.to_string().len()).max().unwrap_or(1)
+}
+
+// One row of cells, each right-aligned to `width`, with missing cells blank.
+fn row<C: Cell>(cells: &[C], start: usize, width: usize) -> String {
+    (start..start + ROW)
+        .map(|address| match cells.get(address) {
+            Some(x) => format!("{:>width$}", x.to_string(), width = width),
+            None => " ".repeat(width),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn dump<C: Cell>(memory: &[C]) -> String {
+    let width = width(memory.iter());
+    let address_width = digits(memory.len().saturating_sub(1));
+    let hints = hints(memory);
+
+    let mut out = String::new();
+    for start in (0..memory.len()).step_by(ROW) {
+        let mut line = format!(
+            "{:>aw$}: {}",
+            start,
+            row(memory, start, width),
+            aw = address_width
+        );
+        for (address, text) in hints
+            .iter()
+            .filter(|(address, _)| (start..start + ROW).contains(address))
+        {
+            line += &format!("  {}: {}", address, text);
+        }
+        out += line.trim_end();
+        out.push('\n');
+    }
+    out
+}
+
+pub fn changes<C: Cell>(before: &[C], after: &[C]) -> Vec<Change<C>> {
+    (0..before.len().max(after.len()))
+        .map(|address| Change {
+            address,
+            before: before.get(address).cloned(),
+            after: after.get(address).cloned(),
+        })
+        .filter(|change| change.before != change.after)
+        .collect()
+}
+
+// Empty when nothing changed.
+pub fn diff<C: Cell>(before: &[C], after: &[C]) -> String {
+    let changes = changes(before, after);
+    let width = width(before.iter().chain(after.iter()));
+    let address_width = digits(before.len().max(after.len()).saturating_sub(1));
+
+    let mut out = String::new();
+    let mut rows: Vec<usize> = changes.iter().map(|c| c.address / ROW * ROW).collect();
+    rows.dedup();
+    for start in rows {
+        let marks = (start..start + ROW)
+            .map(
+                |address| match changes.iter().any(|c| c.address == address) {
+                    true => "^".repeat(width),
+                    false => " ".repeat(width),
+                },
+            )
+            .collect::<Vec<_>>()
+            .join(" ");
+        for (sign, cells) in [('-', before), ('+', after)] {
+            let line = format!(
+                "{}{:>aw$}: {}",
+                sign,
+                start,
+                row(cells, start, width),
+                aw = address_width
+            );
+            out += line.trim_end();
+            out.push('\n');
+        }
+        let line = format!("{} {}", " ".repeat(address_width + 2), marks);
+        out += line.trim_end();
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day_five::run_ops;
+
+    const PROGRAM: [i64; 12] = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+
+    #[test]
+    fn dumps_rows_with_hints() {
+        assert_eq!(
+            dump(&PROGRAM),
+            " 0:  1  9 10  3  2  3 11  0  0: add [9] [10] [3]  4: mul [3] [11] [0]\n\
+             \x208: 99 30 40 50              8: halt\n"
+        );
+
+        let program = [1101i64, -5, 7, 0, 1008, 0, 2, 1, 204, -1, 109, 3, 1105];
+        assert_eq!(
+            dump(&program),
+            " 0: 1101   -5    7    0 1008    0    2    1  0: add -5 7 [0]  4: eq [0] 2 [1]\n\
+             \x208:  204   -1  109    3 1105                 8: out rb[-1]  10: arb 3\n"
+        );
+        assert_eq!(dump::<i64>(&[]), "");
+    }
+
+    #[test]
+    fn skips_what_does_not_decode() {
+        // An immediate write target, a mode that doesn't exist, and a
+        // parameter past the end.
+        let program = [11101i64, 301, 4, 99, 3];
+        assert_eq!(
+            dump(&program),
+            "0: 11101   301     4    99     3                    2: out [99]\n"
+        );
+    }
+
+    #[test]
+    fn diffs_snapshots() {
+        let before = PROGRAM.to_vec();
+        let mut after = before.clone();
+        run_ops(&mut after, 0).unwrap();
+        assert_eq!(
+            changes(&before, &after),
+            [
+                Change {
+                    address: 0,
+                    before: Some(1),
+                    after: Some(3500)
+                },
+                Change {
+                    address: 3,
+                    before: Some(3),
+                    after: Some(70)
+                },
+            ]
+        );
+        assert_eq!(
+            diff(&before, &after),
+            "- 0:    1    9   10    3    2    3   11    0\n\
+             + 0: 3500    9   10   70    2    3   11    0\n\
+             \x20    ^^^^           ^^^^\n"
+        );
+        assert_eq!(diff(&before, &before), "");
+    }
+
+    #[test]
+    fn diffs_different_lengths() {
+        let before = [1i64, 2, 3, 4, 5, 6, 7, 8, 9];
+        let after = [1i64, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        assert_eq!(
+            changes(&before, &after),
+            [Change {
+                address: 9,
+                before: None,
+                after: Some(10)
+            }]
+        );
+        assert_eq!(diff(&before, &after), "-8:  9\n+8:  9 10\n       ^^\n");
+    }
+}
@@ -0,0 +1,497 @@
+// Differential fuzzing of the Intcode interpreters. Programs are generated from
+// a seed so any failure can be reproduced, and failing programs are shrunk
+// before being reported.
+
+use super::{optimize, BigInt, Cell};
+use crate::{day_five, day_two};
+
+const SEEDS: u64 = 500;
+
+// SplitMix64, good enough for test case generation.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+
+    fn value(&mut self) -> i64 {
+        if self.chance(15) {
+            // Large enough that a couple of multiplications overflow i64.
+            let magnitude = 1i64 << (40 + self.below(22));
+            if self.chance(50) {
+                -magnitude
+            } else {
+                magnitude
+            }
+        } else {
+            self.below(201) as i64 - 100
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Param {
+    Imm(i64),
+    // Index into the program's data region.
+    Pos(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Instr {
+    Add(Param, Param, usize),
+    Mul(Param, Param, usize),
+    LessThan(Param, Param, usize),
+    Equals(Param, Param, usize),
+    Input(usize),
+    Out(usize),
+    Loop(i64, Vec<Instr>),
+    // Jump over the body when the condition is true (or false).
+    Skip(bool, Param, Vec<Instr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Program {
+    code: Vec<Instr>,
+    data: Vec<i64>,
+    input: i64,
+}
+
+fn size(code: &[Instr]) -> usize {
+    code.iter()
+        .map(|instr| match instr {
+            Instr::Add(..) | Instr::Mul(..) | Instr::LessThan(..) | Instr::Equals(..) => 4,
+            Instr::Input(_) | Instr::Out(_) => 2,
+            Instr::Loop(_, body) => 11 + size(body),
+            Instr::Skip(_, _, body) => 3 + size(body),
+        })
+        .sum()
+}
+
+fn count_loops(code: &[Instr]) -> usize {
+    code.iter()
+        .map(|instr| match instr {
+            Instr::Loop(_, body) => 1 + count_loops(body),
+            Instr::Skip(_, _, body) => count_loops(body),
+            _ => 0,
+        })
+        .sum()
+}
+
+struct Assembler {
+    ops: Vec<i64>,
+    data_base: usize,
+    next_counter: usize,
+}
+
+impl Assembler {
+    fn param(&mut self, param: &Param) -> i64 {
+        match param {
+            Param::Imm(value) => {
+                self.ops.push(*value);
+                1
+            }
+            Param::Pos(index) => {
+                self.ops.push((self.data_base + index) as i64);
+                0
+            }
+        }
+    }
+
+    fn binary(&mut self, op: i64, a: &Param, b: &Param, target: usize) {
+        let at = self.ops.len();
+        self.ops.push(0);
+        let mode1 = self.param(a);
+        let mode2 = self.param(b);
+        self.ops.push((self.data_base + target) as i64);
+        self.ops[at] = op + 100 * mode1 + 1000 * mode2;
+    }
+
+    fn emit(&mut self, code: &[Instr]) {
+        for instr in code {
+            match instr {
+                Instr::Add(a, b, t) => self.binary(1, a, b, *t),
+                Instr::Mul(a, b, t) => self.binary(2, a, b, *t),
+                Instr::LessThan(a, b, t) => self.binary(7, a, b, *t),
+                Instr::Equals(a, b, t) => self.binary(8, a, b, *t),
+                Instr::Input(t) => self.ops.extend([3, (self.data_base + t) as i64]),
+                Instr::Out(a) => self.ops.extend([4, (self.data_base + a) as i64]),
+                Instr::Loop(count, body) => {
+                    let counter = self.next_counter as i64;
+                    self.next_counter += 1;
+                    self.ops.extend([1101, *count, 0, counter]);
+                    let start = self.ops.len() as i64;
+                    self.emit(body);
+                    self.ops.extend([1001, counter, -1, counter]);
+                    self.ops.extend([1005, counter, start]);
+                }
+                Instr::Skip(when, cond, body) => {
+                    let at = self.ops.len();
+                    self.ops.push(0);
+                    let mode = self.param(cond);
+                    self.ops.push((at + 3 + size(body)) as i64);
+                    self.ops[at] = if *when { 5 } else { 6 } + 100 * mode + 1000;
+                    self.emit(body);
+                }
+            }
+        }
+    }
+}
+
+impl Program {
+    // Code, then a Halt, then the data region, then one counter per loop.
+    fn assemble(&self) -> Vec<i64> {
+        let data_base = size(&self.code) + 1;
+        let mut assembler = Assembler {
+            ops: vec![],
+            data_base,
+            next_counter: data_base + self.data.len(),
+        };
+        assembler.emit(&self.code);
+        assembler.ops.push(99);
+        assembler.ops.extend(&self.data);
+        assembler.ops.extend(vec![0; count_loops(&self.code)]);
+        assembler.ops
+    }
+
+    fn generate(rng: &mut Rng, subset: bool) -> Program {
+        let data: Vec<i64> = (0..1 + rng.below(8)).map(|_| rng.value()).collect();
+        let code = generate_code(rng, data.len(), subset, 0);
+        Program {
+            code,
+            data,
+            input: rng.value(),
+        }
+    }
+}
+
+fn generate_param(rng: &mut Rng, data_len: usize, subset: bool) -> Param {
+    if subset || rng.chance(50) {
+        Param::Pos(rng.below(data_len))
+    } else {
+        Param::Imm(rng.value())
+    }
+}
+
+fn generate_code(rng: &mut Rng, data_len: usize, subset: bool, depth: usize) -> Vec<Instr> {
+    let len = 1 + rng.below(if depth == 0 { 12 } else { 4 });
+    (0..len)
+        .map(|_| {
+            let mut param = || generate_param(rng, data_len, subset);
+            let (a, b) = (param(), param());
+            let choice = if subset { rng.below(2) } else { rng.below(8) };
+            let target = rng.below(data_len);
+            match choice {
+                0 => Instr::Add(a, b, target),
+                1 => Instr::Mul(a, b, target),
+                2 => Instr::LessThan(a, b, target),
+                3 => Instr::Equals(a, b, target),
+                4 => Instr::Input(target),
+                5 => Instr::Out(target),
+                6 if depth < 2 => Instr::Loop(
+                    1 + rng.below(4) as i64,
+                    generate_code(rng, data_len, subset, depth + 1),
+                ),
+                _ if depth < 2 => Instr::Skip(
+                    rng.chance(50),
+                    a,
+                    generate_code(rng, data_len, subset, depth + 1),
+                ),
+                _ => Instr::Out(target),
+            }
+        })
+        .collect()
+}
+
+fn shrink_param(param: &Param) -> Vec<Param> {
+    match param {
+        Param::Imm(0) | Param::Pos(_) => vec![],
+        Param::Imm(value) if value / 2 == 0 => vec![Param::Imm(0)],
+        Param::Imm(value) => vec![Param::Imm(0), Param::Imm(value / 2)],
+    }
+}
+
+fn shrink_code(code: &[Instr]) -> Vec<Vec<Instr>> {
+    let mut candidates = vec![];
+    for (i, instr) in code.iter().enumerate() {
+        let replace = |with: Vec<Instr>| {
+            let mut candidate = code[..i].to_vec();
+            candidate.extend(with);
+            candidate.extend_from_slice(&code[i + 1..]);
+            candidate
+        };
+        candidates.push(replace(vec![]));
+        match instr {
+            Instr::Add(a, b, t)
+            | Instr::Mul(a, b, t)
+            | Instr::LessThan(a, b, t)
+            | Instr::Equals(a, b, t) => {
+                let rebuild = |a: Param, b: Param| match instr {
+                    Instr::Add(..) => Instr::Add(a, b, *t),
+                    Instr::Mul(..) => Instr::Mul(a, b, *t),
+                    Instr::LessThan(..) => Instr::LessThan(a, b, *t),
+                    _ => Instr::Equals(a, b, *t),
+                };
+                for smaller in shrink_param(a) {
+                    candidates.push(replace(vec![rebuild(smaller, b.clone())]));
+                }
+                for smaller in shrink_param(b) {
+                    candidates.push(replace(vec![rebuild(a.clone(), smaller)]));
+                }
+            }
+            Instr::Loop(count, body) => {
+                candidates.push(replace(body.clone()));
+                if *count > 1 {
+                    candidates.push(replace(vec![Instr::Loop(1, body.clone())]));
+                }
+                for smaller in shrink_code(body) {
+                    candidates.push(replace(vec![Instr::Loop(*count, smaller)]));
+                }
+            }
+            Instr::Skip(when, cond, body) => {
+                candidates.push(replace(body.clone()));
+                for smaller in shrink_param(cond) {
+                    candidates.push(replace(vec![Instr::Skip(*when, smaller, body.clone())]));
+                }
+                for smaller in shrink_code(body) {
+                    candidates.push(replace(vec![Instr::Skip(*when, cond.clone(), smaller)]));
+                }
+            }
+            Instr::Input(_) | Instr::Out(_) => {}
+        }
+    }
+    candidates
+}
+
+fn shrink_candidates(program: &Program) -> Vec<Program> {
+    let mut candidates: Vec<Program> = shrink_code(&program.code)
+        .into_iter()
+        .map(|code| Program {
+            code,
+            ..program.clone()
+        })
+        .collect();
+    for (i, value) in program.data.iter().enumerate() {
+        for smaller in shrink_param(&Param::Imm(*value)) {
+            if let Param::Imm(smaller) = smaller {
+                let mut data = program.data.clone();
+                data[i] = smaller;
+                candidates.push(Program {
+                    data,
+                    ..program.clone()
+                });
+            }
+        }
+    }
+    if program.input != 0 {
+        candidates.push(Program {
+            input: 0,
+            ..program.clone()
+        });
+    }
+    candidates
+}
+
+// Greedily applies the first simplification that still fails until none do.
+fn shrink(mut program: Program, fails: impl Fn(&Program) -> bool) -> Program {
+    'outer: loop {
+        for candidate in shrink_candidates(&program) {
+            if fails(&candidate) {
+                program = candidate;
+                continue 'outer;
+            }
+        }
+        return program;
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    memory: Vec<String>,
+    outputs: Vec<String>,
+    error: Option<String>,
+}
+
+fn outcome<C: Cell>(memory: &[C], result: Result<Vec<C>, super::Error<C>>) -> Outcome {
+    let (outputs, error) = match result {
+        Ok(outputs) => (outputs.iter().map(|x| x.to_string()).collect(), None),
+        Err(e) => (vec![], Some(e.to_string())),
+    };
+    Outcome {
+        memory: memory.iter().map(|x| x.to_string()).collect(),
+        outputs,
+        error,
+    }
+}
+
+fn run_day_five<C: Cell>(ops: &[i64], input: i64) -> Outcome {
+    let mut memory: Vec<C> = ops.iter().map(|x| C::from_i64(*x)).collect();
+    let result = day_five::run_ops(&mut memory, C::from_i64(input));
+    outcome(&memory, result)
+}
+
+fn run_day_two<C: Cell>(ops: &[i64]) -> Outcome {
+    let mut memory: Vec<C> = ops.iter().map(|x| C::from_i64(*x)).collect();
+    let result = day_two::perform_ops(&mut memory).map(|_| vec![]);
+    outcome(&memory, result)
+}
+
+// Narrower cells may overflow where wider ones don't, but whenever the
+// narrower run succeeds every wider run must agree with it exactly.
+fn check_widths(program: &Program) -> Option<String> {
+    let ops = program.assemble();
+    let runs = [
+        ("i64", run_day_five::<i64>(&ops, program.input)),
+        ("i128", run_day_five::<i128>(&ops, program.input)),
+        ("BigInt", run_day_five::<BigInt>(&ops, program.input)),
+    ];
+    for (i, (narrow_name, narrow)) in runs.iter().enumerate() {
+        if narrow.error.is_some() {
+            continue;
+        }
+        for (wide_name, wide) in &runs[i + 1..] {
+            if narrow != wide {
+                return Some(format!(
+                    "{} {:?} != {} {:?}",
+                    narrow_name, narrow, wide_name, wide
+                ));
+            }
+        }
+    }
+    None
+}
+
+fn check_subset(program: &Program) -> Option<String> {
+    let ops = program.assemble();
+    let two = run_day_two::<i64>(&ops);
+    let five = run_day_five::<i64>(&ops, program.input);
+    if two != five {
+        return Some(format!("day_two {:?} != day_five {:?}", two, five));
+    }
+    let two = run_day_two::<i128>(&ops);
+    let five = run_day_five::<i128>(&ops, program.input);
+    if two != five {
+        return Some(format!("day_two {:?} != day_five {:?} (i128)", two, five));
+    }
+    None
+}
+
+// The optimizer moves memory around, so only outputs and failure are compared.
+fn check_optimizer(program: &Program) -> Option<String> {
+    let ops = program.assemble();
+    let optimized = optimize::optimize(&ops);
+    let before = run_day_five::<i64>(&ops, program.input);
+    let after = run_day_five::<i64>(&optimized, program.input);
+    if before.outputs != after.outputs || before.error.is_some() != after.error.is_some() {
+        return Some(format!(
+            "{:?} optimized to {:?}: {:?} != {:?}",
+            ops, optimized, before, after
+        ));
+    }
+    None
+}
+
+fn fuzz(subset: bool, check: fn(&Program) -> Option<String>) {
+    for seed in 0..SEEDS {
+        let program = Program::generate(&mut Rng(seed), subset);
+        if let Some(message) = check(&program) {
+            let minimal = shrink(program, |p| check(p).is_some());
+            panic!(
+                "seed {}: {}\nminimal counterexample: {:?}\n{:?}",
+                seed,
+                check(&minimal).unwrap(),
+                minimal.assemble(),
+                message
+            );
+        }
+    }
+}
+
+#[test]
+fn optimizer_preserves_behavior() {
+    fuzz(false, check_optimizer);
+
+    let (mut before, mut after) = (0, 0);
+    for seed in 0..SEEDS {
+        let ops = Program::generate(&mut Rng(seed), false).assemble();
+        before += ops.len();
+        after += optimize::optimize(&ops).len();
+    }
+    assert!(after * 10 < before * 9, "{} -> {}", before, after);
+}
+
+#[test]
+fn day_five_agrees_across_cell_widths() {
+    fuzz(false, check_widths);
+}
+
+#[test]
+fn day_two_agrees_with_day_five() {
+    fuzz(true, check_subset);
+}
+
+#[test]
+fn generated_programs_exercise_overflow() {
+    let overflowed = (0..SEEDS)
+        .map(|seed| Program::generate(&mut Rng(seed), false).assemble())
+        .filter(|ops| run_day_five::<i64>(ops, 0).error.is_some())
+        .count();
+    assert!(overflowed > 0);
+    assert!(overflowed < SEEDS as usize);
+}
+
+#[test]
+fn shrinks_to_minimal_counterexample() {
+    fn has_mul(code: &[Instr]) -> bool {
+        code.iter().any(|instr| match instr {
+            Instr::Mul(..) => true,
+            Instr::Loop(_, body) | Instr::Skip(_, _, body) => has_mul(body),
+            _ => false,
+        })
+    }
+
+    let program = (0..)
+        .map(|seed| Program::generate(&mut Rng(seed), false))
+        .find(|p| has_mul(&p.code) && size(&p.code) > 20)
+        .unwrap();
+    let minimal = shrink(program, |p| has_mul(&p.code));
+
+    assert_eq!(minimal.code.len(), 1);
+    assert!(matches!(minimal.code[0], Instr::Mul(..)));
+    assert!(minimal.data.iter().all(|x| *x == 0));
+    assert_eq!(minimal.input, 0);
+}
+
+#[test]
+fn assembles_loops_and_skips() {
+    let program = Program {
+        code: vec![
+            Instr::Loop(3, vec![Instr::Add(Param::Pos(0), Param::Imm(2), 0)]),
+            Instr::Skip(true, Param::Imm(1), vec![Instr::Out(0)]),
+            Instr::Out(0),
+        ],
+        data: vec![1],
+        input: 0,
+    };
+    let ops = program.assemble();
+    assert_eq!(
+        ops,
+        vec![
+            1101, 3, 0, 24, 1001, 23, 2, 23, 1001, 24, -1, 24, 1005, 24, 4, 1105, 1, 20, 4, 23, 4,
+            23, 99, 1, 0
+        ]
+    );
+    assert_eq!(run_day_five::<i64>(&ops, 0).outputs, vec!["7"]);
+}
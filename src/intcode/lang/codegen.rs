@@ -0,0 +1,442 @@
+// Frames live on a stack addressed through the relative base. A function's
+// frame holds its return address at offset 0, then its parameters, then its
+// locals and the temporaries expressions need. To call, the caller copies the
+// arguments above its own frame, moves the relative base there, stores the
+// return address and jumps; the callee leaves its result in a fixed cell and
+// jumps back through offset 0.
+
+use std::collections::HashMap;
+
+use super::parse::{BinOp, Expr, Function, Pos, Program, Stmt, UnOp};
+use super::CompileError;
+use crate::day_five::OpCode;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Arg {
+    Imm(i64),
+    // The address of a label, as an immediate.
+    Label(usize),
+    // The cell at a label.
+    Global(usize),
+    // A slot in the current frame.
+    Slot(i64),
+    // The current frame's size plus an offset, as an immediate or as a slot
+    // in the frame above.
+    Frame(i64),
+    Above(i64),
+    // Minus the current frame's size.
+    Below,
+}
+
+struct Codegen<'a> {
+    code: Vec<i64>,
+    labels: Vec<Option<usize>>,
+    fixups: Vec<(usize, usize)>,
+    functions: HashMap<&'a str, (usize, usize)>,
+    globals: HashMap<&'a str, usize>,
+    result: usize,
+
+    // The function being compiled.
+    // Cells to set to the frame size times a sign, plus an offset.
+    frame_fixups: Vec<(usize, i64, i64)>,
+    scopes: Vec<HashMap<&'a str, i64>>,
+    locals: i64,
+    temps: i64,
+    frame: i64,
+}
+
+impl<'a> Codegen<'a> {
+    fn label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn bind(&mut self, label: usize) {
+        self.labels[label] = Some(self.code.len());
+    }
+
+    fn emit(&mut self, op: OpCode, args: &[Arg]) {
+        let at = self.code.len();
+        let mut instruction = op as i64;
+        self.code.push(0);
+        for (i, arg) in args.iter().enumerate() {
+            let mode = match arg {
+                Arg::Global(_) => 0,
+                Arg::Slot(_) | Arg::Above(_) => 2,
+                Arg::Imm(_) | Arg::Label(_) | Arg::Frame(_) | Arg::Below => 1,
+            };
+            instruction += mode * 100 * 10i64.pow(i as u32);
+            let value = match *arg {
+                Arg::Imm(value) | Arg::Slot(value) => value,
+                Arg::Label(label) | Arg::Global(label) => {
+                    self.fixups.push((self.code.len(), label));
+                    0
+                }
+                Arg::Frame(offset) | Arg::Above(offset) => {
+                    self.frame_fixups.push((self.code.len(), 1, offset));
+                    0
+                }
+                Arg::Below => {
+                    self.frame_fixups.push((self.code.len(), -1, 0));
+                    0
+                }
+            };
+            self.code.push(value);
+        }
+        self.code[at] = instruction;
+    }
+
+    fn temp(&mut self) -> i64 {
+        self.temps += 1;
+        self.frame = self.frame.max(self.temps);
+        self.temps - 1
+    }
+
+    fn lookup(&self, name: &str, pos: Pos) -> Result<Arg, CompileError> {
+        if let Some(slot) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            return Ok(Arg::Slot(*slot));
+        }
+        match self.globals.get(name) {
+            Some(label) => Ok(Arg::Global(*label)),
+            None => Err(CompileError::new(
+                pos,
+                &format!("undefined variable `{}`", name),
+            )),
+        }
+    }
+
+    fn declare(&mut self, name: &'a str, pos: Pos) -> Result<i64, CompileError> {
+        let slot = self.locals;
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.insert(name, slot).is_some() {
+            return Err(CompileError::new(
+                pos,
+                &format!("`{}` is already declared", name),
+            ));
+        }
+        self.locals += 1;
+        self.temps = self.locals;
+        self.frame = self.frame.max(self.locals);
+        Ok(slot)
+    }
+
+    fn binary(&mut self, op: OpCode, a: Arg, b: Arg) -> Arg {
+        let folded = match (op, a, b) {
+            (OpCode::Add, Arg::Imm(a), Arg::Imm(b)) => a.checked_add(b),
+            (OpCode::Mul, Arg::Imm(a), Arg::Imm(b)) => a.checked_mul(b),
+            (OpCode::LessThan, Arg::Imm(a), Arg::Imm(b)) => Some((a < b) as i64),
+            (OpCode::Equals, Arg::Imm(a), Arg::Imm(b)) => Some((a == b) as i64),
+            _ => None,
+        };
+        if let Some(value) = folded {
+            return Arg::Imm(value);
+        }
+        let t = self.temp();
+        self.emit(op, &[a, b, Arg::Slot(t)]);
+        Arg::Slot(t)
+    }
+
+    fn not(&mut self, a: Arg) -> Arg {
+        self.binary(OpCode::Equals, a, Arg::Imm(0))
+    }
+
+    fn expr(&mut self, expr: &'a Expr) -> Result<Arg, CompileError> {
+        Ok(match expr {
+            Expr::Num(n) => Arg::Imm(*n),
+            Expr::Var(name, pos) => self.lookup(name, *pos)?,
+            Expr::Input => {
+                let t = self.temp();
+                self.emit(OpCode::Input, &[Arg::Slot(t)]);
+                Arg::Slot(t)
+            }
+            Expr::Unary(UnOp::Neg, a) => {
+                let a = self.expr(a)?;
+                self.binary(OpCode::Mul, a, Arg::Imm(-1))
+            }
+            Expr::Unary(UnOp::Not, a) => {
+                let a = self.expr(a)?;
+                self.not(a)
+            }
+            Expr::Binary(op @ (BinOp::And | BinOp::Or), a, b) => {
+                // Short-circuits: the result starts as whatever `a` decides
+                // on its own and is only replaced by `b` when `a` doesn't.
+                let t = self.temp();
+                let end = self.label();
+                let (skip, decided) = match op {
+                    BinOp::And => (OpCode::JumpIfFalse, 0),
+                    _ => (OpCode::JumpIfTrue, 1),
+                };
+                self.emit(OpCode::Add, &[Arg::Imm(decided), Arg::Imm(0), Arg::Slot(t)]);
+                let a = self.expr(a)?;
+                self.emit(skip, &[a, Arg::Label(end)]);
+                let b = self.expr(b)?;
+                self.emit(OpCode::Equals, &[b, Arg::Imm(0), Arg::Slot(t)]);
+                self.emit(OpCode::Equals, &[Arg::Slot(t), Arg::Imm(0), Arg::Slot(t)]);
+                self.bind(end);
+                Arg::Slot(t)
+            }
+            Expr::Binary(op, a, b) => {
+                let a = self.expr(a)?;
+                let b = self.expr(b)?;
+                match op {
+                    BinOp::Add => self.binary(OpCode::Add, a, b),
+                    BinOp::Sub => {
+                        let b = self.binary(OpCode::Mul, b, Arg::Imm(-1));
+                        self.binary(OpCode::Add, a, b)
+                    }
+                    BinOp::Mul => self.binary(OpCode::Mul, a, b),
+                    BinOp::Lt => self.binary(OpCode::LessThan, a, b),
+                    BinOp::Gt => self.binary(OpCode::LessThan, b, a),
+                    BinOp::Le => {
+                        let gt = self.binary(OpCode::LessThan, b, a);
+                        self.not(gt)
+                    }
+                    BinOp::Ge => {
+                        let lt = self.binary(OpCode::LessThan, a, b);
+                        self.not(lt)
+                    }
+                    BinOp::Eq => self.binary(OpCode::Equals, a, b),
+                    BinOp::Ne => {
+                        let eq = self.binary(OpCode::Equals, a, b);
+                        self.not(eq)
+                    }
+                    BinOp::And | BinOp::Or => unreachable!(),
+                }
+            }
+            Expr::Call(name, args, pos) => {
+                let (label, arity) = *self.functions.get(name.as_str()).ok_or_else(|| {
+                    CompileError::new(*pos, &format!("undefined function `{}`", name))
+                })?;
+                if args.len() != arity {
+                    return Err(CompileError::new(
+                        *pos,
+                        &format!(
+                            "`{}` takes {} arguments but {} were given",
+                            name,
+                            arity,
+                            args.len()
+                        ),
+                    ));
+                }
+
+                // Every argument is evaluated before any is copied, since
+                // evaluating one may call a function whose frame overlaps
+                // where the arguments go.
+                let mut values = vec![];
+                for arg in args {
+                    let value = self.expr(arg)?;
+                    let value = match value {
+                        Arg::Global(_) => {
+                            let t = self.temp();
+                            self.emit(OpCode::Add, &[value, Arg::Imm(0), Arg::Slot(t)]);
+                            Arg::Slot(t)
+                        }
+                        _ => value,
+                    };
+                    values.push(value);
+                }
+                for (i, value) in values.into_iter().enumerate() {
+                    self.emit(OpCode::Add, &[value, Arg::Imm(0), Arg::Above(1 + i as i64)]);
+                }
+
+                let back = self.label();
+                self.emit(OpCode::AdjustRelativeBase, &[Arg::Frame(0)]);
+                self.emit(OpCode::Add, &[Arg::Label(back), Arg::Imm(0), Arg::Slot(0)]);
+                self.emit(OpCode::JumpIfTrue, &[Arg::Imm(1), Arg::Label(label)]);
+                self.bind(back);
+                self.emit(OpCode::AdjustRelativeBase, &[Arg::Below]);
+
+                let t = self.temp();
+                self.emit(
+                    OpCode::Add,
+                    &[Arg::Global(self.result), Arg::Imm(0), Arg::Slot(t)],
+                );
+                Arg::Slot(t)
+            }
+        })
+    }
+
+    fn block(&mut self, body: &'a [Stmt]) -> Result<(), CompileError> {
+        self.scopes.push(HashMap::new());
+        for stmt in body {
+            self.stmt(stmt)?;
+            self.temps = self.locals;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn ret(&mut self, value: Arg) {
+        let result = Arg::Global(self.result);
+        self.emit(OpCode::Add, &[value, Arg::Imm(0), result]);
+        self.emit(OpCode::JumpIfFalse, &[Arg::Imm(0), Arg::Slot(0)]);
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt) -> Result<(), CompileError> {
+        match stmt {
+            Stmt::Var(name, init, pos) => {
+                let value = match init {
+                    Some(init) => self.expr(init)?,
+                    None => Arg::Imm(0),
+                };
+                let slot = self.declare(name, *pos)?;
+                self.emit(OpCode::Add, &[value, Arg::Imm(0), Arg::Slot(slot)]);
+            }
+            Stmt::Assign(name, value, pos) => {
+                let target = self.lookup(name, *pos)?;
+                let value = self.expr(value)?;
+                self.emit(OpCode::Add, &[value, Arg::Imm(0), target]);
+            }
+            Stmt::If(cond, then, otherwise) => {
+                let cond = self.expr(cond)?;
+                let (other, end) = (self.label(), self.label());
+                self.emit(OpCode::JumpIfFalse, &[cond, Arg::Label(other)]);
+                self.block(then)?;
+                if !otherwise.is_empty() {
+                    self.emit(OpCode::JumpIfTrue, &[Arg::Imm(1), Arg::Label(end)]);
+                }
+                self.bind(other);
+                self.block(otherwise)?;
+                self.bind(end);
+            }
+            Stmt::While(cond, body) => {
+                let (start, end) = (self.label(), self.label());
+                self.bind(start);
+                let cond = self.expr(cond)?;
+                self.emit(OpCode::JumpIfFalse, &[cond, Arg::Label(end)]);
+                self.block(body)?;
+                self.emit(OpCode::JumpIfTrue, &[Arg::Imm(1), Arg::Label(start)]);
+                self.bind(end);
+            }
+            Stmt::Return(value) => {
+                let value = match value {
+                    Some(value) => self.expr(value)?,
+                    None => Arg::Imm(0),
+                };
+                self.ret(value);
+            }
+            Stmt::Output(value) => {
+                let value = self.expr(value)?;
+                self.emit(OpCode::Out, &[value]);
+            }
+            Stmt::Print(text) => {
+                for c in text.chars() {
+                    self.emit(OpCode::Out, &[Arg::Imm(c as i64)]);
+                }
+            }
+            Stmt::Expr(expr) => {
+                self.expr(expr)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, function: &'a Function) -> Result<(), CompileError> {
+        let label = self.functions[function.name.as_str()].0;
+        self.bind(label);
+
+        self.frame_fixups.clear();
+        self.scopes = vec![HashMap::new()];
+        self.locals = 1;
+        self.temps = 1;
+        self.frame = 1;
+        for param in &function.params {
+            self.declare(param, function.pos)?;
+        }
+        self.block(&function.body)?;
+        self.ret(Arg::Imm(0));
+
+        for (at, sign, offset) in self.frame_fixups.drain(..) {
+            self.code[at] = sign * self.frame + offset;
+        }
+        Ok(())
+    }
+}
+
+pub fn generate(program: &Program, stack: usize) -> Result<Vec<i64>, CompileError> {
+    let mut gen = Codegen {
+        code: vec![],
+        labels: vec![],
+        fixups: vec![],
+        functions: HashMap::new(),
+        globals: HashMap::new(),
+        result: 0,
+        frame_fixups: vec![],
+        scopes: vec![],
+        locals: 0,
+        temps: 0,
+        frame: 0,
+    };
+    gen.result = gen.label();
+
+    for function in &program.functions {
+        let label = gen.label();
+        let name = function.name.as_str();
+        if gen
+            .functions
+            .insert(name, (label, function.params.len()))
+            .is_some()
+        {
+            return Err(CompileError::new(
+                function.pos,
+                &format!("function `{}` is already defined", name),
+            ));
+        }
+    }
+    let main = match gen.functions.get("main") {
+        Some(&(label, 0)) => label,
+        Some(_) => {
+            let pos = program
+                .functions
+                .iter()
+                .find(|f| f.name == "main")
+                .unwrap()
+                .pos;
+            return Err(CompileError::new(pos, "`main` can't take arguments"));
+        }
+        None => {
+            return Err(CompileError::new(
+                Pos { line: 1, column: 1 },
+                "no `main` function",
+            ))
+        }
+    };
+    let mut globals = vec![];
+    for global in &program.globals {
+        let label = gen.label();
+        if gen.globals.insert(&global.name, label).is_some() {
+            return Err(CompileError::new(
+                global.pos,
+                &format!("`{}` is already declared", global.name),
+            ));
+        }
+        globals.push((label, global.value));
+    }
+
+    // Set up the stack and call main, which returns to a halt.
+    let (stack_base, halt) = (gen.label(), gen.label());
+    gen.emit(OpCode::AdjustRelativeBase, &[Arg::Label(stack_base)]);
+    gen.emit(OpCode::Add, &[Arg::Label(halt), Arg::Imm(0), Arg::Slot(0)]);
+    gen.emit(OpCode::JumpIfTrue, &[Arg::Imm(1), Arg::Label(main)]);
+    gen.bind(halt);
+    gen.emit(OpCode::Halt, &[]);
+
+    for function in &program.functions {
+        gen.function(function)?;
+    }
+
+    for (label, value) in globals {
+        gen.bind(label);
+        gen.code.push(value);
+    }
+    let result = gen.result;
+    gen.bind(result);
+    gen.code.push(0);
+    gen.bind(stack_base);
+    gen.code.extend(vec![0; stack]);
+
+    for (at, label) in gen.fixups.drain(..) {
+        gen.code[at] = gen.labels[label].expect("unbound label") as i64;
+    }
+    Ok(gen.code)
+}
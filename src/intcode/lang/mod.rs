@@ -0,0 +1,300 @@
+// A small language that compiles to Intcode using the day five opcodes and
+// the relative base from day nine, which addresses the stack.
+//
+//     var calls = 0;
+//
+//     fn fib(n) {
+//         calls = calls + 1;
+//         if n < 2 { return n; }
+//         return fib(n - 1) + fib(n - 2);
+//     }
+//
+//     fn main() {
+//         var n = input();
+//         while n >= 0 {
+//             output(fib(n));
+//             n = n - 1;
+//         }
+//         output(calls);
+//         print("done\n");
+//     }
+//
+// Values are integers, and zero is false. Expressions have `+ - *`,
+// comparisons, `&& || !` (short-circuiting), calls, `input()` and character
+// literals like `'a'`. Statements are `var`, assignment, `if`/`else`,
+// `while`, `return`, `output(...)`, `print("...")`, which outputs a string a
+// character at a time, and calls. Top-level `var`s are globals initialized
+// with a constant; everything else lives in stack frames addressed through
+// the relative base. Execution starts at `main`, and `//` starts a comment.
+
+mod codegen;
+mod parse;
+
+use std::fmt;
+
+use parse::Pos;
+
+// Cells reserved for the stack when none is given.
+pub const STACK_SIZE: usize = 1024;
+
+#[derive(Debug, PartialEq)]
+pub struct CompileError {
+    // 1-based.
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl CompileError {
+    fn new(pos: Pos, message: &str) -> Self {
+        CompileError {
+            line: pos.line,
+            column: pos.column,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+pub fn compile(source: &str) -> Result<Vec<i64>, CompileError> {
+    compile_with_stack(source, STACK_SIZE)
+}
+
+// The stack is part of the program image, so compiled programs run on
+// machines whose memory doesn't grow, which is the default.
+pub fn compile_with_stack(source: &str, stack: usize) -> Result<Vec<i64>, CompileError> {
+    codegen::generate(&parse::parse(source)?, stack)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::decompile::decompile;
+    use crate::intcode::{Error, Machine, State};
+
+    fn run(source: &str, inputs: &[i64]) -> Vec<i64> {
+        let program = compile(source).unwrap_or_else(|e| panic!("{}", e));
+        let mut machine = Machine::new(program).strict();
+        for input in inputs {
+            machine.push_input(*input);
+        }
+        let mut outputs = vec![];
+        loop {
+            match machine.run() {
+                Ok(State::Output(x)) => outputs.push(x),
+                Ok(State::Halted) => return outputs,
+                other => panic!("{:?} after {:?}", other, outputs),
+            }
+        }
+    }
+
+    fn error(source: &str) -> String {
+        compile(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn evaluates_expressions() {
+        let source = "fn main() {
+            var x = input();
+            var y = input();
+            output(1 + 2 * 3 - -4);
+            output(x - y * 2);
+            output((x + 1) * -(y - 10));
+            output(x < y); output(x > y); output(x <= y);
+            output(x >= y); output(x == y); output(x != y);
+            output(!x); output(!!x);
+        }";
+        assert_eq!(run(source, &[7, 3]), [11, 1, 56, 0, 1, 0, 1, 0, 1, 0, 1]);
+        assert_eq!(run(source, &[3, 3])[3..9], [0, 0, 1, 1, 1, 0]);
+        assert_eq!(run(source, &[0, 5])[9..], [1, 0]);
+    }
+
+    #[test]
+    fn short_circuits() {
+        let source = "
+            fn say(x) { output(x); return x; }
+            fn main() {
+                output(say(0) && say(1));
+                output(say(2) && say(3));
+                output(say(4) || say(5));
+                output(say(0) || say(0));
+            }";
+        assert_eq!(run(source, &[]), [0, 0, 2, 3, 1, 4, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn runs_control_flow() {
+        let source = "fn main() {
+            var n = input();
+            while n > 0 {
+                if n == 2 {
+                    output(200);
+                } else if n > 2 {
+                    output(n * 100);
+                } else {
+                    output(100);
+                }
+                n = n - 1;
+            }
+        }";
+        assert_eq!(run(source, &[4]), [400, 300, 200, 100]);
+        assert_eq!(run(source, &[0]), []);
+    }
+
+    #[test]
+    fn calls_recursive_functions() {
+        let source = "
+            var calls = 0;
+
+            fn fib(n) {
+                calls = calls + 1;
+                if n < 2 { return n; }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn sub(a, b) { return a - b; }
+            fn nothing() { }
+
+            fn main() {
+                output(fib(input()));
+                output(calls);
+                output(sub(10, 3));
+                output(sub(fib(6), sub(5, fib(4))));
+                output(nothing());
+            }";
+        assert_eq!(run(source, &[15]), [610, 1973, 7, 6, 0]);
+    }
+
+    #[test]
+    fn prints_text() {
+        let source = r#"
+            var newline = '\n';
+            fn main() {
+                print("a \"b\"\\ // c");
+                output(newline);
+                output('\'' + 'é');
+                print("");
+            }"#;
+        let text: Vec<i64> = "a \"b\"\\ // c\n".chars().map(|c| c as i64).collect();
+        assert_eq!(run(source, &[]), [text, vec![39 + 233]].concat());
+    }
+
+    #[test]
+    fn scopes_locals() {
+        let source = "
+            var x = -5;
+            fn show() { output(x); }
+            fn main() {
+                show();
+                var x = 1;
+                if x { var x = 2; output(x); }
+                output(x);
+                x = 3;
+                show();
+            }";
+        assert_eq!(run(source, &[]), [-5, 2, 1, -5]);
+    }
+
+    #[test]
+    fn overflows_small_stacks() {
+        let source = "fn down(n) { if n { return down(n - 1); } return 0; }
+            fn main() { output(down(input())); }";
+        let program = compile_with_stack(source, 20).unwrap();
+        let mut machine = Machine::new(program.clone());
+        machine.push_input(3);
+        assert_eq!(machine.run(), Ok(State::Output(0)));
+
+        let mut machine = Machine::new(program);
+        machine.push_input(50);
+        assert!(matches!(machine.run(), Err(Error::InvalidAddress { .. })));
+    }
+
+    #[test]
+    fn decompiles_functions() {
+        let program =
+            compile("fn twice(x) { return x * 2; } fn main() { output(twice(input())); }").unwrap();
+        let source = decompile(&program);
+        assert!(source.contains("void main()"), "{}", source);
+        assert!(source.contains("rb[2] = rb[1] * 2;"), "{}", source);
+        assert!(source.contains("output("), "{}", source);
+    }
+
+    #[test]
+    fn reports_errors_with_positions() {
+        assert_eq!(
+            error("fn main() {\n  output(y);\n}"),
+            "2:10: undefined variable `y`"
+        );
+        assert_eq!(error("fn main() { f(); }"), "1:13: undefined function `f`");
+        assert_eq!(
+            error("fn f(a) {}\nfn main() { f(1, 2); }"),
+            "2:13: `f` takes 1 arguments but 2 were given"
+        );
+        assert_eq!(error("fn f() {}"), "1:1: no `main` function");
+        assert_eq!(error("fn main(x) {}"), "1:4: `main` can't take arguments");
+        assert_eq!(
+            error("fn main() {}\nfn main() {}"),
+            "2:4: function `main` is already defined"
+        );
+        assert_eq!(
+            error("fn main() { var a; var a; }"),
+            "1:24: `a` is already declared"
+        );
+        assert_eq!(
+            error("fn main() { output(1 +); }"),
+            "1:23: expected an expression, found `)`"
+        );
+        assert_eq!(
+            error("fn main() { x = 1 }"),
+            "1:19: expected `;`, found `}`"
+        );
+        assert_eq!(
+            error("fn main() {"),
+            "1:12: expected `}`, found end of input"
+        );
+        assert_eq!(
+            error("fn main() { output(1 % 2); }"),
+            "1:22: unexpected `%`"
+        );
+        assert_eq!(
+            error("fn main() { output(99999999999999999999); }"),
+            "1:20: integer literal is too large"
+        );
+        assert_eq!(error("var g = x;"), "1:9: expected an integer, found `x`");
+        assert_eq!(
+            error("fn main() { print(\"é\"); x }"),
+            "1:27: expected `;`, found `}`"
+        );
+        assert_eq!(
+            error("// é\nfn main() {"),
+            "2:12: expected `}`, found end of input"
+        );
+        assert_eq!(
+            error("fn main() {\n// é"),
+            "2:5: expected `}`, found end of input"
+        );
+        assert_eq!(
+            error("fn main() { print(1); }"),
+            "1:19: expected a string, found `1`"
+        );
+        assert_eq!(
+            error("fn main() { print(\"a); }"),
+            "1:19: unterminated literal"
+        );
+        assert_eq!(
+            error("fn main() { output('ab'); }"),
+            "1:20: expected a single character"
+        );
+        assert_eq!(
+            error("fn main() { output('\\t'); }"),
+            "1:20: unknown escape"
+        );
+    }
+}
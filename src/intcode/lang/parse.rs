@@ -0,0 +1,496 @@
+use super::CompileError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pos {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Num(i64),
+    Str(String),
+    Sym(&'static str),
+    End,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(name) => format!("`{}`", name),
+            Token::Num(n) => format!("`{}`", n),
+            Token::Str(text) => format!("{:?}", text),
+            Token::Sym(s) => format!("`{}`", s),
+            Token::End => "end of input".to_string(),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 19] = [
+    "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "(", ")", "{", "}", ",", ";", "=", "<", ">",
+    "!",
+];
+
+const KEYWORDS: [&str; 9] = [
+    "fn", "var", "if", "else", "while", "return", "input", "output", "print",
+];
+
+// A character of a string or character literal, which may be escaped, and
+// the bytes it took.
+fn literal_char(rest: &str, pos: Pos) -> Result<(char, usize), CompileError> {
+    let mut chars = rest.chars();
+    let c = chars.next().unwrap_or_default();
+    if c != '\\' {
+        return Ok((c, c.len_utf8()));
+    }
+    let escaped = match chars.next() {
+        Some('n') => '\n',
+        Some(c @ ('\\' | '"' | '\'')) => c,
+        _ => return Err(CompileError::new(pos, "unknown escape")),
+    };
+    Ok((escaped, 2))
+}
+
+fn lex(source: &str) -> Result<Vec<(Token, Pos)>, CompileError> {
+    let mut tokens = vec![];
+    for (i, line) in source.lines().enumerate() {
+        let mut rest = line;
+        while !rest.is_empty() {
+            let trimmed = rest.trim_start();
+            let start = line.len() - trimmed.len();
+            // Columns count characters, not bytes.
+            let pos = Pos {
+                line: i + 1,
+                column: line[..start].chars().count() + 1,
+            };
+            rest = trimmed;
+            let c = match rest.chars().next() {
+                Some(c) => c,
+                None => break,
+            };
+
+            if rest.starts_with("//") {
+                break;
+            } else if c == '"' || c == '\'' {
+                let mut text = String::new();
+                let mut len = 1;
+                loop {
+                    if rest.len() == len {
+                        return Err(CompileError::new(pos, "unterminated literal"));
+                    }
+                    if rest[len..].starts_with(c) {
+                        len += 1;
+                        break;
+                    }
+                    let (next, size) = literal_char(&rest[len..], pos)?;
+                    text.push(next);
+                    len += size;
+                }
+                let token = match (c, text.chars().next(), text.chars().count()) {
+                    ('"', _, _) => Token::Str(text),
+                    (_, Some(only), 1) => Token::Num(only as i64),
+                    _ => return Err(CompileError::new(pos, "expected a single character")),
+                };
+                tokens.push((token, pos));
+                rest = &rest[len..];
+            } else if c.is_ascii_digit() {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let n = rest[..len]
+                    .parse()
+                    .map_err(|_| CompileError::new(pos, "integer literal is too large"))?;
+                tokens.push((Token::Num(n), pos));
+                rest = &rest[len..];
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                tokens.push((Token::Ident(rest[..len].to_string()), pos));
+                rest = &rest[len..];
+            } else {
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|s| rest.starts_with(*s))
+                    .ok_or_else(|| CompileError::new(pos, &format!("unexpected `{}`", c)))?;
+                tokens.push((Token::Sym(symbol), pos));
+                rest = &rest[symbol.len()..];
+            }
+        }
+    }
+    let end = Pos {
+        line: source.lines().count().max(1),
+        column: source.lines().last().map_or(0, |line| line.chars().count()) + 1,
+    };
+    tokens.push((Token::End, end));
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(i64),
+    Var(String, Pos),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>, Pos),
+    Input,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Var(String, Option<Expr>, Pos),
+    Assign(String, Expr, Pos),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Return(Option<Expr>),
+    Output(Expr),
+    // Outputs each character of the text.
+    Print(String),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Vec<Stmt>,
+    pub pos: Pos,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub value: i64,
+    pub pos: Pos,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub globals: Vec<Global>,
+    pub functions: Vec<Function>,
+}
+
+struct Parser {
+    tokens: Vec<(Token, Pos)>,
+    next: usize,
+}
+
+// Binary operators from loosest to tightest binding.
+const LEVELS: [&[(&str, BinOp)]; 5] = [
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[("==", BinOp::Eq), ("!=", BinOp::Ne)],
+    &[
+        ("<", BinOp::Lt),
+        (">", BinOp::Gt),
+        ("<=", BinOp::Le),
+        (">=", BinOp::Ge),
+    ],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+];
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    fn pos(&self) -> Pos {
+        self.tokens[self.next].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].0.clone();
+        if token != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, CompileError> {
+        Err(CompileError::new(
+            self.pos(),
+            &format!("expected {}, found {}", expected, self.peek().describe()),
+        ))
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Token::Sym(s) if *s == symbol) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), CompileError> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        self.error(&format!("`{}`", symbol))
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Ident(name) if name == keyword)
+    }
+
+    fn name(&mut self) -> Result<(String, Pos), CompileError> {
+        let pos = self.pos();
+        match self.peek() {
+            Token::Ident(name) if !KEYWORDS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.advance();
+                Ok((name, pos))
+            }
+            _ => self.error("a name"),
+        }
+    }
+
+    fn program(&mut self) -> Result<Program, CompileError> {
+        let mut program = Program {
+            globals: vec![],
+            functions: vec![],
+        };
+        while *self.peek() != Token::End {
+            if self.is_keyword("fn") {
+                program.functions.push(self.function()?);
+            } else if self.is_keyword("var") {
+                program.globals.push(self.global()?);
+            } else {
+                return self.error("`fn` or `var`");
+            }
+        }
+        Ok(program)
+    }
+
+    fn global(&mut self) -> Result<Global, CompileError> {
+        self.advance();
+        let (name, pos) = self.name()?;
+        let mut value = 0;
+        if self.eat("=") {
+            let negative = self.eat("-");
+            value = match *self.peek() {
+                Token::Num(n) if negative => -n,
+                Token::Num(n) => n,
+                _ => return self.error("an integer"),
+            };
+            self.advance();
+        }
+        self.expect(";")?;
+        Ok(Global { name, value, pos })
+    }
+
+    fn function(&mut self) -> Result<Function, CompileError> {
+        self.advance();
+        let (name, pos) = self.name()?;
+        self.expect("(")?;
+        let mut params = vec![];
+        if !self.eat(")") {
+            loop {
+                params.push(self.name()?.0);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        let body = self.block()?;
+        Ok(Function {
+            name,
+            params,
+            body,
+            pos,
+        })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        self.expect("{")?;
+        let mut body = vec![];
+        while !self.eat("}") {
+            if *self.peek() == Token::End {
+                return self.error("`}`");
+            }
+            body.push(self.statement()?);
+        }
+        Ok(body)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, CompileError> {
+        if self.is_keyword("var") {
+            self.advance();
+            let (name, pos) = self.name()?;
+            let init = match self.eat("=") {
+                true => Some(self.expr()?),
+                false => None,
+            };
+            self.expect(";")?;
+            return Ok(Stmt::Var(name, init, pos));
+        }
+        if self.is_keyword("if") {
+            self.advance();
+            let cond = self.expr()?;
+            let then = self.block()?;
+            let otherwise = if !self.is_keyword("else") {
+                vec![]
+            } else {
+                self.advance();
+                if self.is_keyword("if") {
+                    vec![self.statement()?]
+                } else {
+                    self.block()?
+                }
+            };
+            return Ok(Stmt::If(cond, then, otherwise));
+        }
+        if self.is_keyword("while") {
+            self.advance();
+            let cond = self.expr()?;
+            return Ok(Stmt::While(cond, self.block()?));
+        }
+        if self.is_keyword("return") {
+            self.advance();
+            let value = match self.eat(";") {
+                true => return Ok(Stmt::Return(None)),
+                false => self.expr()?,
+            };
+            self.expect(";")?;
+            return Ok(Stmt::Return(Some(value)));
+        }
+        if self.is_keyword("output") {
+            self.advance();
+            self.expect("(")?;
+            let value = self.expr()?;
+            self.expect(")")?;
+            self.expect(";")?;
+            return Ok(Stmt::Output(value));
+        }
+        if self.is_keyword("print") {
+            self.advance();
+            self.expect("(")?;
+            let Token::Str(text) = self.peek().clone() else {
+                return self.error("a string");
+            };
+            self.advance();
+            self.expect(")")?;
+            self.expect(";")?;
+            return Ok(Stmt::Print(text));
+        }
+
+        let after = self.tokens.get(self.next + 1).map(|(token, _)| token);
+        if let (Token::Ident(_), Some(Token::Sym("="))) = (self.peek(), after) {
+            let (name, pos) = self.name()?;
+            self.advance();
+            let value = self.expr()?;
+            self.expect(";")?;
+            return Ok(Stmt::Assign(name, value, pos));
+        }
+        let expr = self.expr()?;
+        self.expect(";")?;
+        Ok(Stmt::Expr(expr))
+    }
+
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, CompileError> {
+        if level == LEVELS.len() {
+            return self.product();
+        }
+        let mut left = self.binary(level + 1)?;
+        'outer: loop {
+            for (symbol, op) in LEVELS[level] {
+                if self.eat(symbol) {
+                    let right = self.binary(level + 1)?;
+                    left = Expr::Binary(*op, Box::new(left), Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, CompileError> {
+        let mut left = self.unary()?;
+        while self.eat("*") {
+            let right = self.unary()?;
+            left = Expr::Binary(BinOp::Mul, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat("-") {
+            return Ok(Expr::Unary(UnOp::Neg, Box::new(self.unary()?)));
+        }
+        if self.eat("!") {
+            return Ok(Expr::Unary(UnOp::Not, Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat("(") {
+            let expr = self.expr()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        if let Token::Num(n) = *self.peek() {
+            self.advance();
+            return Ok(Expr::Num(n));
+        }
+        if self.is_keyword("input") {
+            self.advance();
+            self.expect("(")?;
+            self.expect(")")?;
+            return Ok(Expr::Input);
+        }
+
+        let (name, pos) = match self.name() {
+            Ok(name) => name,
+            Err(_) => return self.error("an expression"),
+        };
+        if !self.eat("(") {
+            return Ok(Expr::Var(name, pos));
+        }
+        let mut args = vec![];
+        if !self.eat(")") {
+            loop {
+                args.push(self.expr()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        Ok(Expr::Call(name, args, pos))
+    }
+}
+
+pub fn parse(source: &str) -> Result<Program, CompileError> {
+    let mut parser = Parser {
+        tokens: lex(source)?,
+        next: 0,
+    };
+    parser.program()
+}
@@ -0,0 +1,196 @@
+// Loads comma-separated Intcode programs. Whitespace around values is ignored,
+// `#` starts a comment that runs to the end of the line, and a trailing comma
+// is allowed.
+
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    // Offsets are bytes from the start of the input; lines and columns are
+    // 1-based.
+    InvalidToken {
+        token: String,
+        offset: usize,
+        line: usize,
+        column: usize,
+    },
+    MissingToken {
+        offset: usize,
+        line: usize,
+        column: usize,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "failed to read program: {}", e),
+            LoadError::InvalidToken {
+                token,
+                offset,
+                line,
+                column,
+            } => write!(
+                f,
+                "invalid value {:?} at offset {} (line {}, column {})",
+                token, offset, line, column
+            ),
+            LoadError::MissingToken {
+                offset,
+                line,
+                column,
+            } => write!(
+                f,
+                "missing value at offset {} (line {}, column {})",
+                offset, line, column
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<std::io::Error> for LoadError {
+    fn from(e: std::io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+fn position(s: &str, offset: usize) -> (usize, usize) {
+    let before = &s[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+pub fn parse<C: FromStr>(s: &str) -> Result<Vec<C>, LoadError> {
+    // Blank out comments so offsets into `s` stay valid.
+    let mut code = String::with_capacity(s.len());
+    for line in s.split_inclusive('\n') {
+        match line.find('#') {
+            Some(i) => {
+                let (comment, newline) = match line[i..].strip_suffix('\n') {
+                    Some(comment) => (comment, "\n"),
+                    None => (&line[i..], ""),
+                };
+                code.push_str(&line[..i]);
+                code.push_str(&" ".repeat(comment.len()));
+                code.push_str(newline);
+            }
+            None => code.push_str(line),
+        }
+    }
+
+    let mut ops = vec![];
+    let mut start = 0;
+    let pieces: Vec<&str> = code.split(',').collect();
+    for (i, piece) in pieces.iter().enumerate() {
+        let token = piece.trim();
+        let offset = start + piece.len() - piece.trim_start().len();
+        start += piece.len() + 1;
+
+        if token.is_empty() {
+            // Empty input, or a trailing comma.
+            if i == pieces.len() - 1 && (i == 0 || !ops.is_empty()) {
+                break;
+            }
+            let (line, column) = position(s, offset);
+            return Err(LoadError::MissingToken {
+                offset,
+                line,
+                column,
+            });
+        }
+
+        match token.parse() {
+            Ok(value) => ops.push(value),
+            Err(_) => {
+                let (line, column) = position(s, offset);
+                return Err(LoadError::InvalidToken {
+                    token: s[offset..offset + token.len()].to_string(),
+                    offset,
+                    line,
+                    column,
+                });
+            }
+        }
+    }
+
+    Ok(ops)
+}
+
+pub fn read<C: FromStr>(mut reader: impl Read) -> Result<Vec<C>, LoadError> {
+    let mut s = String::new();
+    reader.read_to_string(&mut s)?;
+    parse(&s)
+}
+
+pub fn load<C: FromStr>(path: impl AsRef<Path>) -> Result<Vec<C>, LoadError> {
+    read(std::fs::File::open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::BigInt;
+
+    #[test]
+    fn tolerates_whitespace_and_comments() {
+        let program =
+            "# doubles its input — in place\n3, 7,\n  1002,7,2,7, # in place\r\n4,7,99,\n";
+        let ops: Vec<i64> = parse(program).unwrap();
+        assert_eq!(ops, vec![3, 7, 1002, 7, 2, 7, 4, 7, 99]);
+    }
+
+    #[test]
+    fn empty_input_is_an_empty_program() {
+        assert_eq!(parse::<i64>("").unwrap(), vec![]);
+        assert_eq!(parse::<i64>(" \n# nothing here\n").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn reports_invalid_tokens() {
+        match parse::<i64>("1,2, # ünïcode\n3,x4,99") {
+            Err(LoadError::InvalidToken {
+                token,
+                offset,
+                line,
+                column,
+            }) => {
+                assert_eq!(token, "x4");
+                assert_eq!(offset, 19);
+                assert_eq!((line, column), (2, 3));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_missing_tokens() {
+        match parse::<i64>("1, ,2") {
+            Err(LoadError::MissingToken { offset, .. }) => assert_eq!(offset, 3),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            parse::<i64>(",1"),
+            Err(LoadError::MissingToken { offset: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn reads_from_readers_and_paths() {
+        let ops: Vec<BigInt> = read("1,99999999999999999999999,99".as_bytes()).unwrap();
+        assert_eq!(ops[1].to_string(), "99999999999999999999999");
+
+        let ops: Vec<i64> = load("resources/day_two_sample.txt").unwrap();
+        assert_eq!(ops.len(), 12);
+        assert!(matches!(
+            load::<i64>("resources/missing.txt"),
+            Err(LoadError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,464 @@
+// A resumable Intcode machine. `run` executes until the program halts, outputs
+// a value, or needs input that hasn't been pushed yet, so callers can feed it
+// input and consume its output as it goes.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use super::memory::Memory;
+use super::observer::{Observer, Verdict};
+use super::registry::{Instruction, Registry, Role};
+use super::{Cell, Error};
+use crate::day_five::{decode, Mode, OpCode};
+
+// The most memory a growable machine will use, so a stray address fails
+// instead of allocating gigabytes.
+pub const MAX_MEMORY: usize = 1 << 24;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum State<C> {
+    Halted,
+    NeedsInput,
+    Output(C),
+    // An observer vetoed part of the instruction at `ip`, which hasn't run.
+    Vetoed,
+}
+
+#[derive(Debug, Clone)]
+pub struct Machine<C = i64> {
+    memory: Memory<C>,
+    ip: usize,
+    base: i64,
+    strict: bool,
+    growable: bool,
+    inputs: VecDeque<C>,
+    registry: Option<Arc<Registry<C>>>,
+}
+
+// Why an instruction didn't complete.
+enum Interrupt<C> {
+    Veto,
+    Fail(Error<C>),
+}
+
+impl<C> From<Error<C>> for Interrupt<C> {
+    fn from(e: Error<C>) -> Self {
+        Interrupt::Fail(e)
+    }
+}
+
+fn check<C>(verdict: Verdict) -> Result<(), Interrupt<C>> {
+    match verdict {
+        Verdict::Allow => Ok(()),
+        Verdict::Veto => Err(Interrupt::Veto),
+    }
+}
+
+impl<C: Cell> Machine<C> {
+    pub fn new(program: Vec<C>) -> Self {
+        Self::from_memory(Memory::new(program))
+    }
+
+    // Starts a machine on a copy-on-write clone of `memory`, so machines
+    // started from the same image share the pages none of them write to.
+    pub fn from_memory(memory: Memory<C>) -> Self {
+        Machine {
+            memory,
+            ip: 0,
+            base: 0,
+            strict: false,
+            growable: false,
+            inputs: VecDeque::new(),
+            registry: None,
+        }
+    }
+
+    // Rejects immediate-mode write targets and instructions with digits beyond
+    // the modes their opcode uses.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    // Lets the program use addresses past the end of its memory, which read as
+    // zero until they're written, as programs from day nine on expect.
+    pub fn growable(mut self) -> Self {
+        self.growable = true;
+        self
+    }
+
+    // Opcodes the built-in instruction set doesn't use are looked up here.
+    pub fn with_registry(mut self, registry: Arc<Registry<C>>) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub fn push_input(&mut self, value: C) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.base
+    }
+
+    pub fn memory(&self) -> &Memory<C> {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory<C> {
+        &mut self.memory
+    }
+
+    pub fn into_memory(self) -> Memory<C> {
+        self.memory
+    }
+
+    pub fn run(&mut self) -> Result<State<C>, Error<C>> {
+        self.run_with(&mut ())
+    }
+
+    pub fn run_with(&mut self, observer: &mut impl Observer<C>) -> Result<State<C>, Error<C>> {
+        loop {
+            match self.step(observer) {
+                Ok(None) => {}
+                Ok(Some(state)) => return Ok(state),
+                Err(Interrupt::Veto) => return Ok(State::Vetoed),
+                Err(Interrupt::Fail(e)) => return Err(e),
+            }
+        }
+    }
+
+    // Nothing is changed until every hook for the instruction has allowed it,
+    // so a veto or an error leaves the machine where it was.
+    fn step(&mut self, observer: &mut impl Observer<C>) -> Result<Option<State<C>>, Interrupt<C>> {
+        let ip = self.ip;
+        let mut instruction = self.read(ip)?;
+        // Pause before the fetch hook so an instruction that waits for input
+        // is only seen once, but only after checking it could take the input.
+        let code = instruction.to_usize().map(|x| x % 100);
+        if code == Some(OpCode::Input as usize) && self.inputs.is_empty() {
+            let (_, modes) = self.decode(instruction.clone())?;
+            self.target(1, modes[0])?;
+            return Ok(Some(State::NeedsInput));
+        }
+        check(observer.on_fetch(ip, &mut instruction))?;
+
+        if let Some(registry) = self.registry.clone() {
+            let custom = instruction.to_usize().and_then(|x| registry.get(x % 100));
+            if let Some(custom) = custom {
+                return self.step_custom(observer, custom, instruction);
+            }
+        }
+
+        let (op, modes) = self.decode(instruction)?;
+
+        match op {
+            OpCode::Add | OpCode::Mul => {
+                let arg1 = self.param(observer, 1, modes[0])?;
+                let arg2 = self.param(observer, 2, modes[1])?;
+                let target = self.target(3, modes[2])?;
+                let result = match op {
+                    OpCode::Add => arg1.checked_add(&arg2),
+                    _ => arg1.checked_mul(&arg2),
+                };
+                let result = result.ok_or(Error::Overflow { ip })?;
+                self.write(observer, target, result)?;
+                self.ip += 4;
+            }
+            OpCode::LessThan | OpCode::Equals => {
+                let arg1 = self.param(observer, 1, modes[0])?;
+                let arg2 = self.param(observer, 2, modes[1])?;
+                let target = self.target(3, modes[2])?;
+                let result = match op {
+                    OpCode::LessThan => arg1 < arg2,
+                    _ => arg1 == arg2,
+                };
+                self.write(observer, target, C::from_i64(result as i64))?;
+                self.ip += 4;
+            }
+            OpCode::Input => {
+                let target = self.target(1, modes[0])?;
+                let mut value = match self.inputs.front() {
+                    Some(value) => value.clone(),
+                    None => return Ok(Some(State::NeedsInput)),
+                };
+                check(observer.on_input(ip, &mut value))?;
+                self.write(observer, target, value)?;
+                self.inputs.pop_front();
+                self.ip += 2;
+            }
+            OpCode::Out => {
+                let mut value = self.param(observer, 1, modes[0])?;
+                check(observer.on_output(ip, &mut value))?;
+                self.ip += 2;
+                return Ok(Some(State::Output(value)));
+            }
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let arg1 = self.param(observer, 1, modes[0])?;
+                let arg2 = self.param(observer, 2, modes[1])?;
+                if arg1.is_zero() == (op == OpCode::JumpIfFalse) {
+                    self.ip = self.to_address(arg2)?;
+                } else {
+                    self.ip += 3;
+                }
+            }
+            OpCode::AdjustRelativeBase => {
+                let arg = self.param(observer, 1, modes[0])?;
+                self.base = arg
+                    .to_i64()
+                    .and_then(|x| x.checked_add(self.base))
+                    .ok_or(Error::Overflow { ip })?;
+                self.ip += 2;
+            }
+            OpCode::Halt => {
+                check(observer.on_halt(ip))?;
+                return Ok(Some(State::Halted));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Observers see every read, then every write and the output, before any of
+    // them take effect.
+    fn step_custom(
+        &mut self,
+        observer: &mut impl Observer<C>,
+        custom: &Instruction<C>,
+        instruction: C,
+    ) -> Result<Option<State<C>>, Interrupt<C>> {
+        let ip = self.ip;
+        let modes = self.decode_custom(custom, instruction.clone())?;
+
+        let mut args = vec![];
+        let mut targets = vec![];
+        for (i, (role, mode)) in custom.roles.iter().zip(modes).enumerate() {
+            match role {
+                Role::Read => args.push(self.param(observer, i + 1, mode)?),
+                Role::Write => targets.push(self.target(i + 1, mode)?),
+            }
+        }
+
+        let effect = custom.execute(&args);
+        if effect.writes.len() != targets.len() {
+            let value = instruction;
+            return Err(Error::InvalidOpCode { ip, value }.into());
+        }
+        let jump = match effect.jump {
+            Some(address) => Some(self.to_address(address)?),
+            None => None,
+        };
+        let mut writes = vec![];
+        for (address, mut value) in targets.into_iter().zip(effect.writes) {
+            check(observer.on_write(ip, address, &mut value))?;
+            writes.push((address, value));
+        }
+        let output = match effect.output {
+            Some(mut value) => {
+                check(observer.on_output(ip, &mut value))?;
+                Some(value)
+            }
+            None => None,
+        };
+
+        for (address, value) in writes {
+            self.store(address, value);
+        }
+        self.ip = jump.unwrap_or(ip + 1 + custom.roles.len());
+        Ok(output.map(State::Output))
+    }
+
+    fn decode_custom(&self, custom: &Instruction<C>, value: C) -> Result<Vec<Mode>, Error<C>> {
+        let ip = self.ip;
+        let mut digits = value.to_usize().unwrap_or_default() / 100;
+        let mut modes = vec![];
+        for role in &custom.roles {
+            let mode = match Mode::try_from(digits % 10) {
+                Ok(mode) => mode,
+                Err(()) => return Err(Error::InvalidOpCode { ip, value }),
+            };
+            if self.strict && *role == Role::Write && mode == Mode::Immediate {
+                return Err(Error::ImmediateWrite { ip });
+            }
+            modes.push(mode);
+            digits /= 10;
+        }
+
+        if self.strict && digits != 0 {
+            return Err(Error::InvalidOpCode { ip, value });
+        }
+        Ok(modes)
+    }
+
+    // Returns the opcode and the modes of its parameters, first to third.
+    fn decode(&self, value: C) -> Result<(OpCode, [Mode; 3]), Error<C>> {
+        let ip = self.ip;
+        let x = value.to_usize();
+        let (x, (mode3, mode2, mode1, op)) = match (x, x.and_then(decode)) {
+            (Some(x), Some(decoded)) => (x, decoded),
+            _ => return Err(Error::InvalidOpCode { ip, value }),
+        };
+        let modes = [mode1, mode2, mode3];
+
+        if self.strict {
+            let unused_modes = modes[op.params()..].iter().any(|m| *m != Mode::Position);
+            if x >= 100000 || unused_modes {
+                return Err(Error::InvalidOpCode { ip, value });
+            }
+            if let Some(param) = op.write_param() {
+                if modes[param - 1] == Mode::Immediate {
+                    return Err(Error::ImmediateWrite { ip });
+                }
+            }
+        }
+
+        Ok((op, modes))
+    }
+
+    fn in_bounds(&self, address: usize) -> bool {
+        address < self.memory.len() || (self.growable && address < MAX_MEMORY)
+    }
+
+    fn read(&self, address: usize) -> Result<C, Error<C>> {
+        match self.memory.get(address) {
+            Some(value) => Ok(value.clone()),
+            None if self.in_bounds(address) => Ok(C::zero()),
+            None => Err(Error::InvalidAddress {
+                ip: self.ip,
+                address: C::from_i64(address as i64),
+            }),
+        }
+    }
+
+    fn to_address(&self, value: C) -> Result<usize, Error<C>> {
+        match value.to_usize() {
+            Some(address) if self.in_bounds(address) => Ok(address),
+            _ => Err(Error::InvalidAddress {
+                ip: self.ip,
+                address: value,
+            }),
+        }
+    }
+
+    fn address(&self, arg: C, mode: Mode) -> Result<usize, Error<C>> {
+        let arg = match mode {
+            Mode::Relative => match arg.to_i64().and_then(|x| x.checked_add(self.base)) {
+                Some(x) => C::from_i64(x),
+                None => {
+                    return Err(Error::InvalidAddress {
+                        ip: self.ip,
+                        address: arg,
+                    })
+                }
+            },
+            Mode::Position | Mode::Immediate => arg,
+        };
+        self.to_address(arg)
+    }
+
+    fn param(
+        &self,
+        observer: &mut impl Observer<C>,
+        offset: usize,
+        mode: Mode,
+    ) -> Result<C, Interrupt<C>> {
+        let arg = self.read(self.ip + offset)?;
+        match mode {
+            Mode::Immediate => Ok(arg),
+            Mode::Position | Mode::Relative => {
+                let address = self.address(arg, mode)?;
+                let mut value = self.read(address)?;
+                check(observer.on_read(self.ip, address, &mut value))?;
+                Ok(value)
+            }
+        }
+    }
+
+    // Write targets in immediate mode are treated as position mode unless strict.
+    fn target(&self, offset: usize, mode: Mode) -> Result<usize, Error<C>> {
+        self.address(self.read(self.ip + offset)?, mode)
+    }
+
+    fn write(
+        &mut self,
+        observer: &mut impl Observer<C>,
+        address: usize,
+        mut value: C,
+    ) -> Result<(), Interrupt<C>> {
+        check(observer.on_write(self.ip, address, &mut value))?;
+        self.store(address, value);
+        Ok(())
+    }
+
+    fn store(&mut self, address: usize, value: C) {
+        self.memory.grow(address + 1, C::zero());
+        self.memory.set(address, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pauses_for_input_and_output() {
+        let mut machine = Machine::new(vec![3i64, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0]);
+        assert_eq!(machine.run(), Ok(State::NeedsInput));
+        assert_eq!(machine.ip(), 0);
+        machine.push_input(2);
+        assert_eq!(machine.run(), Ok(State::NeedsInput));
+        machine.push_input(3);
+        assert_eq!(machine.run(), Ok(State::Output(5)));
+        assert_eq!(machine.run(), Ok(State::Halted));
+        assert_eq!(machine.run(), Ok(State::Halted));
+    }
+
+    #[test]
+    fn errors_leave_the_machine_in_place() {
+        let mut machine = Machine::new(vec![1101i64, 1, 2, 9, 1, 50, 0, 0, 99, 0]);
+        assert_eq!(
+            machine.run(),
+            Err(Error::InvalidAddress { ip: 4, address: 50 })
+        );
+        assert_eq!(machine.ip(), 4);
+        assert_eq!(machine.memory()[9], 3);
+    }
+
+    #[test]
+    fn checks_input_instructions_before_waiting() {
+        let mut machine = Machine::new(vec![103i64, 0, 99]).strict();
+        assert_eq!(machine.run(), Err(Error::ImmediateWrite { ip: 0 }));
+        let mut machine = Machine::new(vec![3i64, -1, 99]);
+        assert_eq!(
+            machine.run(),
+            Err(Error::InvalidAddress { ip: 0, address: -1 })
+        );
+        let mut machine = Machine::new(vec![3i64, 0, 99]).strict();
+        assert_eq!(machine.run(), Ok(State::NeedsInput));
+    }
+
+    #[test]
+    fn grows_memory_when_asked() {
+        // Copies the cell past the end into a cell further out, then outputs
+        // both.
+        let program = vec![1001i64, 20, 5, 30, 4, 20, 4, 30, 99];
+        let mut machine = Machine::new(program.clone());
+        assert_eq!(
+            machine.run(),
+            Err(Error::InvalidAddress { ip: 0, address: 20 })
+        );
+
+        let mut machine = Machine::new(program).growable();
+        assert_eq!(machine.run(), Ok(State::Output(0)));
+        assert_eq!(machine.run(), Ok(State::Output(5)));
+        assert_eq!(machine.run(), Ok(State::Halted));
+        assert_eq!(machine.memory().len(), 31);
+
+        let mut machine = Machine::new(vec![4i64, MAX_MEMORY as i64]).growable();
+        assert!(matches!(machine.run(), Err(Error::InvalidAddress { .. })));
+    }
+}
@@ -0,0 +1,177 @@
+// Paged copy-on-write memory. Clones share every page with the original, and a
+// write copies only the page it lands in (plus the page table, the first time),
+// so thousands of machines can run from one loaded image.
+
+use std::ops::Index;
+use std::sync::Arc;
+
+pub const PAGE_SIZE: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct Memory<C> {
+    pages: Arc<Vec<Arc<Vec<C>>>>,
+    len: usize,
+}
+
+impl<C: Clone> Memory<C> {
+    pub fn new(cells: Vec<C>) -> Self {
+        let len = cells.len();
+        let pages = cells
+            .chunks(PAGE_SIZE)
+            .map(|page| Arc::new(page.to_vec()))
+            .collect();
+        Memory {
+            pages: Arc::new(pages),
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, address: usize) -> Option<&C> {
+        if address >= self.len {
+            return None;
+        }
+        Some(&self.pages[address / PAGE_SIZE][address % PAGE_SIZE])
+    }
+
+    // Panics if `address` is out of bounds, like slice indexing.
+    pub fn set(&mut self, address: usize, value: C) {
+        assert!(
+            address < self.len,
+            "address {} out of bounds for memory of length {}",
+            address,
+            self.len
+        );
+        let page = &mut Arc::make_mut(&mut self.pages)[address / PAGE_SIZE];
+        Arc::make_mut(page)[address % PAGE_SIZE] = value;
+    }
+
+    // Extends memory to `len` cells, filling new cells with `value`. Only the
+    // last page is copied, and only if it was partly full.
+    pub fn grow(&mut self, len: usize, value: C) {
+        if len <= self.len {
+            return;
+        }
+        let pages = Arc::make_mut(&mut self.pages);
+        if let Some(last) = pages.last_mut().filter(|page| page.len() < PAGE_SIZE) {
+            let fill = (PAGE_SIZE - last.len()).min(len - self.len);
+            Arc::make_mut(last).extend(std::iter::repeat_n(value.clone(), fill));
+            self.len += fill;
+        }
+        while self.len < len {
+            let fill = PAGE_SIZE.min(len - self.len);
+            pages.push(Arc::new(vec![value.clone(); fill]));
+            self.len += fill;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &C> {
+        self.pages.iter().flat_map(|page| page.iter())
+    }
+
+    pub fn to_vec(&self) -> Vec<C> {
+        self.iter().cloned().collect()
+    }
+
+    // The number of pages this memory still shares with `other`.
+    pub fn shared_pages(&self, other: &Memory<C>) -> usize {
+        self.pages
+            .iter()
+            .zip(other.pages.iter())
+            .filter(|(a, b)| Arc::ptr_eq(a, b))
+            .count()
+    }
+}
+
+impl<C: Clone> From<Vec<C>> for Memory<C> {
+    fn from(cells: Vec<C>) -> Self {
+        Memory::new(cells)
+    }
+}
+
+impl<C: Clone> Index<usize> for Memory<C> {
+    type Output = C;
+
+    fn index(&self, address: usize) -> &C {
+        match self.get(address) {
+            Some(value) => value,
+            None => panic!(
+                "address {} out of bounds for memory of length {}",
+                address, self.len
+            ),
+        }
+    }
+}
+
+impl<C: Clone + PartialEq> PartialEq for Memory<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{loader, Machine, State};
+
+    #[test]
+    fn copies_only_written_pages() {
+        let image = Memory::new((0..200i64).collect());
+        assert_eq!(image.len(), 200);
+        assert_eq!((image[0], image[199], image.get(200)), (0, 199, None));
+
+        let mut copy = image.clone();
+        assert_eq!(copy.shared_pages(&image), 4);
+        copy.set(70, -1);
+        copy.set(71, -2);
+        assert_eq!(copy.shared_pages(&image), 3);
+        assert_eq!((image[70], copy[70], copy[71]), (70, -1, -2));
+        assert_ne!(copy, image);
+
+        copy.set(70, 70);
+        copy.set(71, 71);
+        assert_eq!(copy, image);
+        assert_eq!(copy.to_vec(), (0..200).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn grows_without_copying_full_pages() {
+        let image = Memory::new((0..100i64).collect());
+        let mut grown = image.clone();
+        grown.grow(300, -1);
+        assert_eq!(grown.len(), 300);
+        assert_eq!(grown.shared_pages(&image), 1);
+        assert_eq!((grown[99], grown[100], grown[299]), (99, -1, -1));
+        assert_eq!(grown.iter().filter(|x| **x == -1).count(), 200);
+
+        grown.grow(10, 0);
+        assert_eq!(grown.len(), 300);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn rejects_writes_past_the_end() {
+        Memory::new(vec![0i64; 3]).set(3, 1);
+    }
+
+    #[test]
+    fn machines_share_a_loaded_image() {
+        let image: Memory<i64> = loader::load::<i64>("resources/day_five_input.txt")
+            .unwrap()
+            .into();
+        let mut machine = Machine::from_memory(image.clone());
+        machine.push_input(1);
+        while let Ok(State::Output(_)) = machine.run() {}
+
+        assert_eq!(machine.run(), Ok(State::Halted));
+        assert!(machine.memory().shared_pages(&image) > 0);
+        assert_eq!(Machine::from_memory(image.clone()).memory(), &image);
+    }
+}
@@ -0,0 +1,50 @@
+pub mod ascii;
+mod bigint;
+mod cell;
+#[cfg(test)]
+mod conformance;
+pub mod decompile;
+pub mod dump;
+#[cfg(test)]
+mod fuzz;
+pub mod lang;
+pub mod loader;
+mod machine;
+mod memory;
+pub mod observer;
+pub mod optimize;
+pub mod registry;
+pub mod replay;
+pub mod symbolic;
+
+use std::fmt;
+
+pub use bigint::{BigInt, ParseBigIntError};
+pub use cell::Cell;
+pub use machine::{Machine, State, MAX_MEMORY};
+pub use memory::{Memory, PAGE_SIZE};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error<C> {
+    Overflow { ip: usize },
+    InvalidOpCode { ip: usize, value: C },
+    InvalidAddress { ip: usize, address: C },
+    ImmediateWrite { ip: usize },
+}
+
+impl<C: fmt::Display> fmt::Display for Error<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Overflow { ip } => write!(f, "arithmetic overflow at ip {}", ip),
+            Error::InvalidOpCode { ip, value } => {
+                write!(f, "invalid opcode {} at ip {}", value, ip)
+            }
+            Error::InvalidAddress { ip, address } => {
+                write!(f, "invalid address {} at ip {}", address, ip)
+            }
+            Error::ImmediateWrite { ip } => write!(f, "immediate mode write at ip {}", ip),
+        }
+    }
+}
+
+impl<C: fmt::Debug + fmt::Display> std::error::Error for Error<C> {}
@@ -0,0 +1,294 @@
+// Hooks into a running Machine. Every hook sees the instruction pointer of the
+// instruction being executed and may rewrite the value passed to it. Vetoing
+// stops the machine before the instruction has any effect, so running it
+// again retries that instruction from the start.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Verdict {
+    Allow,
+    Veto,
+}
+
+pub trait Observer<C> {
+    fn on_fetch(&mut self, _ip: usize, _instruction: &mut C) -> Verdict {
+        Verdict::Allow
+    }
+
+    // Reads of position and relative mode parameters.
+    fn on_read(&mut self, _ip: usize, _address: usize, _value: &mut C) -> Verdict {
+        Verdict::Allow
+    }
+
+    fn on_write(&mut self, _ip: usize, _address: usize, _value: &mut C) -> Verdict {
+        Verdict::Allow
+    }
+
+    fn on_input(&mut self, _ip: usize, _value: &mut C) -> Verdict {
+        Verdict::Allow
+    }
+
+    fn on_output(&mut self, _ip: usize, _value: &mut C) -> Verdict {
+        Verdict::Allow
+    }
+
+    fn on_halt(&mut self, _ip: usize) -> Verdict {
+        Verdict::Allow
+    }
+}
+
+impl<C> Observer<C> for () {}
+
+impl<C, O: Observer<C>> Observer<C> for &mut O {
+    fn on_fetch(&mut self, ip: usize, instruction: &mut C) -> Verdict {
+        (**self).on_fetch(ip, instruction)
+    }
+
+    fn on_read(&mut self, ip: usize, address: usize, value: &mut C) -> Verdict {
+        (**self).on_read(ip, address, value)
+    }
+
+    fn on_write(&mut self, ip: usize, address: usize, value: &mut C) -> Verdict {
+        (**self).on_write(ip, address, value)
+    }
+
+    fn on_input(&mut self, ip: usize, value: &mut C) -> Verdict {
+        (**self).on_input(ip, value)
+    }
+
+    fn on_output(&mut self, ip: usize, value: &mut C) -> Verdict {
+        (**self).on_output(ip, value)
+    }
+
+    fn on_halt(&mut self, ip: usize) -> Verdict {
+        (**self).on_halt(ip)
+    }
+}
+
+fn both(a: Verdict, b: impl FnOnce() -> Verdict) -> Verdict {
+    match a {
+        Verdict::Allow => b(),
+        Verdict::Veto => Verdict::Veto,
+    }
+}
+
+// Runs the first observer, then the second with whatever the first left in
+// the value. The second isn't called once the first vetoes.
+impl<C, A: Observer<C>, B: Observer<C>> Observer<C> for (A, B) {
+    fn on_fetch(&mut self, ip: usize, instruction: &mut C) -> Verdict {
+        both(self.0.on_fetch(ip, instruction), || {
+            self.1.on_fetch(ip, instruction)
+        })
+    }
+
+    fn on_read(&mut self, ip: usize, address: usize, value: &mut C) -> Verdict {
+        both(self.0.on_read(ip, address, value), || {
+            self.1.on_read(ip, address, value)
+        })
+    }
+
+    fn on_write(&mut self, ip: usize, address: usize, value: &mut C) -> Verdict {
+        both(self.0.on_write(ip, address, value), || {
+            self.1.on_write(ip, address, value)
+        })
+    }
+
+    fn on_input(&mut self, ip: usize, value: &mut C) -> Verdict {
+        both(self.0.on_input(ip, value), || self.1.on_input(ip, value))
+    }
+
+    fn on_output(&mut self, ip: usize, value: &mut C) -> Verdict {
+        both(self.0.on_output(ip, value), || self.1.on_output(ip, value))
+    }
+
+    fn on_halt(&mut self, ip: usize) -> Verdict {
+        both(self.0.on_halt(ip), || self.1.on_halt(ip))
+    }
+}
+
+// Records every event as a line of text.
+#[derive(Debug, Default)]
+pub struct Tracer {
+    pub lines: Vec<String>,
+}
+
+impl<C: Display> Observer<C> for Tracer {
+    fn on_fetch(&mut self, ip: usize, instruction: &mut C) -> Verdict {
+        self.lines.push(format!("{}: fetch {}", ip, instruction));
+        Verdict::Allow
+    }
+
+    fn on_read(&mut self, ip: usize, address: usize, value: &mut C) -> Verdict {
+        self.lines
+            .push(format!("{}: read [{}] = {}", ip, address, value));
+        Verdict::Allow
+    }
+
+    fn on_write(&mut self, ip: usize, address: usize, value: &mut C) -> Verdict {
+        self.lines
+            .push(format!("{}: write [{}] = {}", ip, address, value));
+        Verdict::Allow
+    }
+
+    fn on_input(&mut self, ip: usize, value: &mut C) -> Verdict {
+        self.lines.push(format!("{}: input {}", ip, value));
+        Verdict::Allow
+    }
+
+    fn on_output(&mut self, ip: usize, value: &mut C) -> Verdict {
+        self.lines.push(format!("{}: output {}", ip, value));
+        Verdict::Allow
+    }
+
+    fn on_halt(&mut self, ip: usize) -> Verdict {
+        self.lines.push(format!("{}: halt", ip));
+        Verdict::Allow
+    }
+}
+
+// Counts fetches per address and per opcode. An instruction that another
+// observer vetoes is counted again when it's retried.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    pub by_ip: BTreeMap<usize, usize>,
+    pub by_opcode: BTreeMap<i64, usize>,
+}
+
+impl Profiler {
+    pub fn total(&self) -> usize {
+        self.by_ip.values().sum()
+    }
+
+    // The `n` most executed addresses, most executed first.
+    pub fn hottest(&self, n: usize) -> Vec<(usize, usize)> {
+        let mut counts: Vec<(usize, usize)> = self.by_ip.iter().map(|(&k, &v)| (k, v)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts.truncate(n);
+        counts
+    }
+}
+
+impl<C: super::Cell> Observer<C> for Profiler {
+    fn on_fetch(&mut self, ip: usize, instruction: &mut C) -> Verdict {
+        *self.by_ip.entry(ip).or_default() += 1;
+        if let Some(x) = instruction.to_i64() {
+            *self.by_opcode.entry(x % 100).or_default() += 1;
+        }
+        Verdict::Allow
+    }
+}
+
+// Stops the machine before any write to a watched address. Running again lets
+// that write through.
+#[derive(Debug, Default)]
+pub struct Watchpoint {
+    pub addresses: BTreeSet<usize>,
+    pub hits: Vec<(usize, usize)>,
+    pending: Option<(usize, usize)>,
+}
+
+impl Watchpoint {
+    pub fn new(addresses: impl IntoIterator<Item = usize>) -> Self {
+        Watchpoint {
+            addresses: addresses.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl<C> Observer<C> for Watchpoint {
+    fn on_write(&mut self, ip: usize, address: usize, _value: &mut C) -> Verdict {
+        if self.pending.take() == Some((ip, address)) || !self.addresses.contains(&address) {
+            return Verdict::Allow;
+        }
+        self.pending = Some((ip, address));
+        self.hits.push((ip, address));
+        Verdict::Veto
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{Machine, State};
+
+    #[test]
+    fn traces_every_event() {
+        let mut machine = Machine::new(vec![3i64, 9, 1002, 9, 2, 9, 4, 9, 99, 0]);
+        machine.push_input(21);
+        let mut tracer = Tracer::default();
+        assert_eq!(machine.run_with(&mut tracer), Ok(State::Output(42)));
+        assert_eq!(machine.run_with(&mut tracer), Ok(State::Halted));
+        assert_eq!(
+            tracer.lines,
+            [
+                "0: fetch 3",
+                "0: input 21",
+                "0: write [9] = 21",
+                "2: fetch 1002",
+                "2: read [9] = 21",
+                "2: write [9] = 42",
+                "6: fetch 4",
+                "6: read [9] = 42",
+                "6: output 42",
+                "8: fetch 99",
+                "8: halt",
+            ]
+        );
+    }
+
+    struct Doubler;
+
+    impl Observer<i64> for Doubler {
+        fn on_input(&mut self, _ip: usize, value: &mut i64) -> Verdict {
+            *value *= 2;
+            Verdict::Allow
+        }
+
+        fn on_output(&mut self, _ip: usize, value: &mut i64) -> Verdict {
+            *value += 1;
+            Verdict::Allow
+        }
+    }
+
+    #[test]
+    fn rewrites_values() {
+        let mut machine = Machine::new(vec![3i64, 0, 4, 0, 99]);
+        machine.push_input(5);
+        assert_eq!(machine.run_with(&mut Doubler), Ok(State::Output(11)));
+        assert_eq!(machine.memory()[0], 10);
+    }
+
+    #[test]
+    fn veto_leaves_the_instruction_undone() {
+        let mut machine = Machine::new(vec![3i64, 7, 1001, 7, 1, 7, 99, 0]);
+        machine.push_input(5);
+        let mut watch = Watchpoint::new([7]);
+
+        assert_eq!(machine.run_with(&mut watch), Ok(State::Vetoed));
+        assert_eq!((machine.ip(), machine.memory()[7]), (0, 0));
+        assert_eq!(machine.run_with(&mut watch), Ok(State::Vetoed));
+        assert_eq!((machine.ip(), machine.memory()[7]), (2, 5));
+        assert_eq!(machine.run_with(&mut watch), Ok(State::Halted));
+        assert_eq!(machine.memory()[7], 6);
+        assert_eq!(watch.hits, [(0, 7), (2, 7)]);
+    }
+
+    #[test]
+    fn combines_observers() {
+        let mut machine = Machine::new(vec![1101i64, 1, 2, 0, 1101, 3, 4, 0, 99]);
+        let mut profiler = Profiler::default();
+        let mut watch = Watchpoint::new([0]);
+        let mut both = (&mut profiler, &mut watch);
+        while machine.run_with(&mut both) == Ok(State::Vetoed) {}
+
+        assert_eq!(machine.memory()[0], 7);
+        assert_eq!(watch.hits.len(), 2);
+        // Both adds are fetched twice: once when vetoed and once when retried.
+        assert_eq!(profiler.by_opcode, BTreeMap::from([(1, 4), (99, 1)]));
+        assert_eq!(profiler.total(), 5);
+        assert_eq!(profiler.hottest(1), [(0, 2)]);
+    }
+}
@@ -0,0 +1,376 @@
+// Shrinks Intcode programs without changing their input and output. Control
+// flow is followed from address 0, using every cell that no reachable
+// instruction writes to as a constant, and then:
+//
+// - position-mode reads of never-written cells become immediates
+// - Add/Mul/LessThan/Equals with only immediate operands store their result
+//   directly, and jumps with an immediate condition become unconditional or
+//   are dropped
+// - unreachable code and data no instruction refers to are removed, and the
+//   remaining cells are packed together
+//
+// The memory layout changes, so programs whose results are read out of memory
+// afterwards aren't equivalent. Programs that use relative mode, modify their
+// own code, jump to addresses that can't be determined, or run off the end of
+// memory are returned unchanged.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{Machine, State};
+use crate::day_five::{decode, Mode, OpCode};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operand {
+    Imm(i64),
+    Pos(usize),
+}
+
+#[derive(Clone, Debug)]
+struct Instr {
+    addr: usize,
+    op: OpCode,
+    params: Vec<Operand>,
+}
+
+impl Instr {
+    fn next(&self) -> usize {
+        self.addr + 1 + self.params.len()
+    }
+
+    fn cells(&self) -> std::ops::Range<usize> {
+        self.addr..self.next()
+    }
+
+    fn is_jump(&self) -> bool {
+        matches!(self.op, OpCode::JumpIfTrue | OpCode::JumpIfFalse)
+    }
+
+    fn writes(&self) -> Option<usize> {
+        match self.op.write_param().map(|i| self.params[i - 1]) {
+            Some(Operand::Pos(addr)) => Some(addr),
+            _ => None,
+        }
+    }
+}
+
+fn decode_at(ops: &[i64], addr: usize) -> Option<Instr> {
+    let (mode3, mode2, mode1, op) = usize::try_from(*ops.get(addr)?).ok().and_then(decode)?;
+    let modes = [mode1, mode2, mode3];
+    let params = (0..op.params())
+        .map(|i| {
+            let value = *ops.get(addr + 1 + i)?;
+            let write = op.write_param() == Some(i + 1);
+            match modes[i] {
+                Mode::Immediate if !write => Some(Operand::Imm(value)),
+                // Immediate-mode write targets are treated as positions.
+                Mode::Position | Mode::Immediate => usize::try_from(value)
+                    .ok()
+                    .filter(|addr| *addr < ops.len())
+                    .map(Operand::Pos),
+                Mode::Relative => None,
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Instr { addr, op, params })
+}
+
+struct Analysis<'a> {
+    ops: &'a [i64],
+    written: BTreeSet<usize>,
+}
+
+impl Analysis<'_> {
+    fn value(&self, operand: Operand) -> Option<i64> {
+        match operand {
+            Operand::Imm(value) => Some(value),
+            Operand::Pos(addr) if !self.written.contains(&addr) => Some(self.ops[addr]),
+            Operand::Pos(_) => None,
+        }
+    }
+
+    // Where control can go after `instr`.
+    fn successors(&self, instr: &Instr) -> Option<Vec<usize>> {
+        match instr.op {
+            OpCode::Halt => Some(vec![]),
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let target = usize::try_from(self.value(instr.params[1])?).ok()?;
+                let taken = self
+                    .value(instr.params[0])
+                    .map(|cond| (cond != 0) == (instr.op == OpCode::JumpIfTrue));
+                Some(match taken {
+                    Some(true) => vec![target],
+                    Some(false) => vec![instr.next()],
+                    None => vec![instr.next(), target],
+                })
+            }
+            _ => Some(vec![instr.next()]),
+        }
+    }
+
+    fn reachable(&self) -> Option<BTreeMap<usize, Instr>> {
+        let mut instrs = BTreeMap::new();
+        let mut pending = vec![0];
+        while let Some(addr) = pending.pop() {
+            if instrs.contains_key(&addr) {
+                continue;
+            }
+            let instr = decode_at(self.ops, addr)?;
+            pending.extend(self.successors(&instr)?);
+            instrs.insert(addr, instr);
+        }
+        Some(instrs)
+    }
+}
+
+// Assumes nothing is written and grows the written set until it matches what
+// the reachable code writes. Each round only makes more code reachable.
+fn analyze(ops: &[i64]) -> Option<(BTreeMap<usize, Instr>, BTreeSet<usize>)> {
+    let mut analysis = Analysis {
+        ops,
+        written: BTreeSet::new(),
+    };
+    loop {
+        let instrs = analysis.reachable()?;
+        let written: BTreeSet<usize> = instrs.values().filter_map(Instr::writes).collect();
+        if written == analysis.written {
+            return Some((instrs, written));
+        }
+        analysis.written = written;
+    }
+}
+
+// Replaces constant reads with immediates and folds what that makes constant.
+// Returns None for instructions that can be dropped.
+fn simplify(analysis: &Analysis, instr: &Instr) -> Option<Instr> {
+    let mut instr = instr.clone();
+    let reads = instr.op.params() - instr.op.write_param().map_or(0, |_| 1);
+    for param in &mut instr.params[..reads] {
+        if let Some(value) = analysis.value(*param) {
+            *param = Operand::Imm(value);
+        }
+    }
+
+    match (instr.op, &instr.params[..]) {
+        (
+            OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equals,
+            &[Operand::Imm(a), Operand::Imm(b), target],
+        ) => {
+            let result = match instr.op {
+                OpCode::Add => a.checked_add(b),
+                OpCode::Mul => a.checked_mul(b),
+                OpCode::LessThan => Some((a < b) as i64),
+                _ => Some((a == b) as i64),
+            };
+            // Overflow is left for the machine to report.
+            if let Some(result) = result {
+                instr.op = OpCode::Add;
+                instr.params = vec![Operand::Imm(result), Operand::Imm(0), target];
+            }
+        }
+        (OpCode::JumpIfTrue | OpCode::JumpIfFalse, &[Operand::Imm(cond), Operand::Imm(target)]) => {
+            let taken = (cond != 0) == (instr.op == OpCode::JumpIfTrue);
+            if !taken || target as usize == instr.next() {
+                return None;
+            }
+            instr.op = OpCode::JumpIfTrue;
+            instr.params[0] = Operand::Imm(1);
+        }
+        _ => {}
+    }
+    Some(instr)
+}
+
+fn encode(instr: &Instr, relocate: &[usize]) -> Vec<i64> {
+    let mut code = instr.op as i64;
+    let mut params = vec![];
+    for (i, param) in instr.params.iter().enumerate() {
+        match *param {
+            Operand::Imm(value) if instr.is_jump() && i == 1 => {
+                code += 100 * 10i64.pow(i as u32);
+                params.push(relocate[value as usize] as i64);
+            }
+            Operand::Imm(value) => {
+                code += 100 * 10i64.pow(i as u32);
+                params.push(value);
+            }
+            Operand::Pos(addr) => params.push(relocate[addr] as i64),
+        }
+    }
+    std::iter::once(code).chain(params).collect()
+}
+
+pub fn optimize(ops: &[i64]) -> Vec<i64> {
+    // Removing code can turn jumps into jumps to the next instruction, so
+    // repeat until nothing more comes out.
+    let mut program = optimize_once(ops);
+    loop {
+        let next = optimize_once(&program);
+        if next.len() >= program.len() {
+            return program;
+        }
+        program = next;
+    }
+}
+
+fn optimize_once(ops: &[i64]) -> Vec<i64> {
+    let (instrs, written) = match analyze(ops) {
+        Some(result) => result,
+        None => return ops.to_vec(),
+    };
+
+    let mut code = BTreeSet::new();
+    for instr in instrs.values() {
+        for addr in instr.cells() {
+            if !code.insert(addr) {
+                // Overlapping instructions.
+                return ops.to_vec();
+            }
+        }
+    }
+    if !written.is_disjoint(&code) {
+        return ops.to_vec();
+    }
+
+    let analysis = Analysis { ops, written };
+    let simplified: BTreeMap<usize, Option<Instr>> = instrs
+        .values()
+        .map(|instr| (instr.addr, simplify(&analysis, instr)))
+        .collect();
+    let referenced: BTreeSet<usize> = simplified
+        .values()
+        .flatten()
+        .flat_map(|instr| &instr.params)
+        .filter_map(|param| match param {
+            Operand::Pos(addr) => Some(*addr),
+            Operand::Imm(_) => None,
+        })
+        .collect();
+
+    // Dropped instructions only fall through, so anything jumping to one can
+    // jump to whatever follows it instead. They're kept if their cells are
+    // read or written as data.
+    let mut kept = vec![false; ops.len()];
+    let mut emit = BTreeMap::new();
+    for (addr, instr) in &simplified {
+        let original = &instrs[addr];
+        let instr = match instr {
+            Some(instr) => instr.clone(),
+            None if original.cells().any(|a| referenced.contains(&a)) => original.clone(),
+            None => continue,
+        };
+        for a in instr.cells() {
+            kept[a] = true;
+        }
+        emit.insert(*addr, instr);
+    }
+    for addr in &referenced {
+        kept[*addr] = true;
+    }
+
+    // Old addresses map to the new address of the first kept cell at or
+    // after them.
+    let mut relocate = Vec::with_capacity(ops.len() + 1);
+    let mut next = 0;
+    for keep in &kept {
+        relocate.push(next);
+        next += *keep as usize;
+    }
+    relocate.push(next);
+
+    let mut program = vec![];
+    let mut addr = 0;
+    while addr < ops.len() {
+        if let Some(instr) = emit.get(&addr) {
+            program.extend(encode(instr, &relocate));
+            addr = instr.next();
+        } else {
+            if kept[addr] {
+                program.push(ops[addr]);
+            }
+            addr += 1;
+        }
+    }
+    program
+}
+
+#[derive(Debug, PartialEq)]
+enum Run {
+    Halted(Vec<i64>),
+    NeedsInput(Vec<i64>),
+    Failed(Vec<i64>),
+}
+
+fn run(ops: &[i64], inputs: &[i64], max_outputs: usize) -> Run {
+    let mut machine = Machine::new(ops.to_vec());
+    for input in inputs {
+        machine.push_input(*input);
+    }
+    let mut outputs = vec![];
+    loop {
+        match machine.run() {
+            Ok(State::Output(x)) if outputs.len() < max_outputs => outputs.push(x),
+            Ok(State::Halted) => return Run::Halted(outputs),
+            Ok(State::NeedsInput) => return Run::NeedsInput(outputs),
+            _ => return Run::Failed(outputs),
+        }
+    }
+}
+
+// Runs both programs on `inputs` and checks that they produce the same
+// outputs and stop the same way. `max_outputs` bounds programs that never
+// stop.
+pub fn verify(original: &[i64], optimized: &[i64], inputs: &[i64], max_outputs: usize) -> bool {
+    run(original, inputs, max_outputs) == run(optimized, inputs, max_outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::loader;
+
+    #[test]
+    fn folds_constants_and_reads() {
+        // [20] = (2 + 3) * 10; out [20]; out [21] < 4
+        let program = [
+            1101, 2, 3, 20, 1002, 20, 10, 20, 4, 20, 1007, 21, 4, 22, 4, 22, 99, 0, 0, 0, 0, 3, 0,
+        ];
+        let optimized = optimize(&program);
+        assert_eq!(
+            optimized,
+            [1101, 5, 0, 17, 1002, 17, 10, 17, 4, 17, 1101, 1, 0, 18, 4, 18, 99, 0, 0]
+        );
+        assert_eq!(run(&optimized, &[], 10), Run::Halted(vec![50, 1]));
+        assert!(verify(&program, &optimized, &[], 10));
+    }
+
+    #[test]
+    fn removes_dead_branches() {
+        // if [10] { out 1 }; out 2
+        let program = [1006, 10, 5, 104, 1, 104, 2, 99, 0, 0, 0];
+        let optimized = optimize(&program);
+        assert_eq!(optimized, [104, 2, 99]);
+        assert!(verify(&program, &optimized, &[], 10));
+    }
+
+    #[test]
+    fn keeps_loops_over_written_cells() {
+        // n = input; while n != 0 { out n; n -= 1 }
+        let program = [
+            3, 15, 1006, 15, 14, 4, 15, 1001, 15, -1, 15, 1105, 1, 2, 99, 0,
+        ];
+        let optimized = optimize(&program);
+        assert_eq!(optimized, program);
+        for n in 0..5 {
+            assert!(verify(&program, &optimized, &[n], 10));
+        }
+    }
+
+    #[test]
+    fn leaves_unsupported_programs_alone() {
+        let day_five: Vec<i64> = loader::load("resources/day_five_input.txt").unwrap();
+        assert_eq!(optimize(&day_five), day_five);
+        assert_eq!(optimize(&[109, 1, 204, -1, 99]), [109, 1, 204, -1, 99]);
+        assert_eq!(optimize(&[1105, 1, 50]), [1105, 1, 50]);
+        assert_eq!(optimize(&[1101, 1, 1, 3]), [1101, 1, 1, 3]);
+    }
+}
@@ -0,0 +1,310 @@
+// Custom instructions for Machine. Each one has an opcode that doesn't clash
+// with the built-in ones, a role per parameter, and a closure that is handed
+// the values of its read parameters and says what the instruction does.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::day_five::OpCode;
+
+// Parameter modes are stored one digit per parameter above the opcode, and
+// a usize has room for this many of them.
+pub const MAX_PARAMS: usize = 17;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Role {
+    Read,
+    Write,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Effect<C> {
+    // One value per write parameter, in order. Any other number of values
+    // makes the instruction invalid.
+    pub writes: Vec<C>,
+    pub output: Option<C>,
+    pub jump: Option<C>,
+}
+
+impl<C> Effect<C> {
+    pub fn none() -> Self {
+        Effect {
+            writes: vec![],
+            output: None,
+            jump: None,
+        }
+    }
+
+    pub fn write(values: Vec<C>) -> Self {
+        Effect {
+            writes: values,
+            ..Effect::none()
+        }
+    }
+
+    pub fn output(mut self, value: C) -> Self {
+        self.output = Some(value);
+        self
+    }
+
+    pub fn jump(mut self, address: C) -> Self {
+        self.jump = Some(address);
+        self
+    }
+}
+
+type Exec<C> = Box<dyn Fn(&[C]) -> Effect<C> + Send + Sync>;
+
+pub struct Instruction<C> {
+    pub name: String,
+    pub roles: Vec<Role>,
+    exec: Exec<C>,
+}
+
+impl<C> Instruction<C> {
+    // `exec` runs again if an observer vetoes one of the instruction's writes
+    // or its output and the machine is resumed.
+    pub fn new(
+        name: &str,
+        roles: &[Role],
+        exec: impl Fn(&[C]) -> Effect<C> + Send + Sync + 'static,
+    ) -> Self {
+        Instruction {
+            name: name.to_string(),
+            roles: roles.to_vec(),
+            exec: Box::new(exec),
+        }
+    }
+
+    pub fn execute(&self, args: &[C]) -> Effect<C> {
+        (self.exec)(args)
+    }
+}
+
+impl<C> fmt::Debug for Instruction<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Instruction")
+            .field("name", &self.name)
+            .field("roles", &self.roles)
+            .finish()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RegisterError {
+    Reserved(usize),
+    Duplicate(usize),
+    TooManyParams(usize),
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegisterError::Reserved(code) => write!(f, "opcode {} is reserved", code),
+            RegisterError::Duplicate(code) => write!(f, "opcode {} is already registered", code),
+            RegisterError::TooManyParams(n) => {
+                write!(f, "{} parameters is more than {}", n, MAX_PARAMS)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RegisterError {}
+
+#[derive(Debug)]
+pub struct Registry<C> {
+    instructions: BTreeMap<usize, Instruction<C>>,
+}
+
+impl<C> Default for Registry<C> {
+    fn default() -> Self {
+        Registry {
+            instructions: BTreeMap::new(),
+        }
+    }
+}
+
+impl<C> Registry<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(
+        &mut self,
+        code: usize,
+        instruction: Instruction<C>,
+    ) -> Result<(), RegisterError> {
+        if code == 0 || code >= 100 || OpCode::try_from(code).is_ok() {
+            return Err(RegisterError::Reserved(code));
+        }
+        if self.instructions.contains_key(&code) {
+            return Err(RegisterError::Duplicate(code));
+        }
+        if instruction.roles.len() > MAX_PARAMS {
+            return Err(RegisterError::TooManyParams(instruction.roles.len()));
+        }
+        self.instructions.insert(code, instruction);
+        Ok(())
+    }
+
+    pub fn get(&self, code: usize) -> Option<&Instruction<C>> {
+        self.instructions.get(&code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::observer::Tracer;
+    use crate::intcode::{Error, Machine, State};
+    use std::sync::{Arc, Mutex};
+
+    fn registry() -> Registry<i64> {
+        let mut registry = Registry::new();
+        // Integer division: 10 a b c sets c to a / b.
+        registry
+            .register(
+                10,
+                Instruction::new("div", &[Role::Read, Role::Read, Role::Write], |args| {
+                    Effect::write(vec![args[0] / args[1]])
+                }),
+            )
+            .unwrap();
+        // Outputs the sum of its first two arguments and jumps to the third.
+        // The fourth is read but unused.
+        registry
+            .register(
+                11,
+                Instruction::new(
+                    "sum",
+                    &[Role::Read, Role::Read, Role::Read, Role::Read],
+                    |args| Effect::none().output(args[0] + args[1]).jump(args[2]),
+                ),
+            )
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    fn rejects_reserved_and_duplicate_codes() {
+        let mut registry = registry();
+        let nop = || Instruction::new("nop", &[], |_: &[i64]| Effect::none());
+        assert_eq!(registry.register(9, nop()), Err(RegisterError::Reserved(9)));
+        assert_eq!(
+            registry.register(99, nop()),
+            Err(RegisterError::Reserved(99))
+        );
+        assert_eq!(
+            registry.register(120, nop()),
+            Err(RegisterError::Reserved(120))
+        );
+        assert_eq!(
+            registry.register(10, nop()),
+            Err(RegisterError::Duplicate(10))
+        );
+        let wide = Instruction::new("wide", &[Role::Read; 18], |_: &[i64]| Effect::none());
+        assert_eq!(
+            registry.register(12, wide),
+            Err(RegisterError::TooManyParams(18))
+        );
+    }
+
+    #[test]
+    fn runs_custom_instructions() {
+        let program = vec![1010, 13, 4, 13, 11011, 13, 1, 12, 0, 0, 0, 0, 99, 84];
+        let mut machine = Machine::new(program).with_registry(Arc::new(registry()));
+        let mut tracer = Tracer::default();
+
+        assert_eq!(machine.run_with(&mut tracer), Ok(State::Output(22)));
+        assert_eq!(machine.run(), Ok(State::Halted));
+        assert_eq!(machine.memory()[13], 21);
+        assert_eq!(
+            tracer.lines[..4],
+            [
+                "0: fetch 1010",
+                "0: read [13] = 84",
+                "0: write [13] = 21",
+                "4: fetch 11011"
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_custom_modes() {
+        let registry = Arc::new(registry());
+        let mut machine = Machine::new(vec![11110, 8, 2, 0, 99]).with_registry(registry.clone());
+        assert_eq!(machine.run(), Ok(State::Halted));
+        assert_eq!(machine.memory()[0], 4);
+
+        let mut machine = Machine::new(vec![11110, 8, 2, 0, 99])
+            .with_registry(registry.clone())
+            .strict();
+        assert_eq!(machine.run(), Err(Error::ImmediateWrite { ip: 0 }));
+
+        let mut machine = Machine::new(vec![101110, 8, 2, 0, 99])
+            .with_registry(registry.clone())
+            .strict();
+        assert_eq!(
+            machine.run(),
+            Err(Error::InvalidOpCode {
+                ip: 0,
+                value: 101110
+            })
+        );
+
+        let mut machine = Machine::new(vec![310, 8, 2, 0, 99]).with_registry(registry);
+        assert_eq!(
+            machine.run(),
+            Err(Error::InvalidOpCode { ip: 0, value: 310 })
+        );
+    }
+
+    #[test]
+    fn checks_write_counts() {
+        let mut registry = Registry::new();
+        let roles = [Role::Write, Role::Write];
+        registry
+            .register(
+                12,
+                Instruction::new("one", &roles, |_| Effect::write(vec![1])),
+            )
+            .unwrap();
+        registry
+            .register(
+                13,
+                Instruction::new("three", &roles, |_| Effect::write(vec![1, 2, 3])),
+            )
+            .unwrap();
+        let registry = Arc::new(registry);
+
+        for code in [12i64, 13] {
+            let mut machine = Machine::new(vec![code, 0, 1, 99]).with_registry(registry.clone());
+            assert_eq!(
+                machine.run(),
+                Err(Error::InvalidOpCode { ip: 0, value: code })
+            );
+            assert_eq!(machine.memory()[0], code);
+        }
+    }
+
+    #[test]
+    fn closures_can_have_side_effects() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut registry = Registry::new();
+        let debug = log.clone();
+        registry
+            .register(
+                42,
+                Instruction::new("debug", &[Role::Read], move |args| {
+                    debug.lock().unwrap().push(args[0]);
+                    Effect::none()
+                }),
+            )
+            .unwrap();
+
+        let mut machine =
+            Machine::new(vec![142i64, 7, 42, 0, 4, 0, 99]).with_registry(Arc::new(registry));
+        assert_eq!(machine.run(), Ok(State::Output(142)));
+        assert_eq!(*log.lock().unwrap(), [7, 142]);
+    }
+}
@@ -0,0 +1,395 @@
+// Records the inputs a machine consumes and the outputs it produces, each
+// stamped with the number of instructions fetched so far, so a session can be
+// saved and replayed exactly. Replay files are plain text:
+//
+//     in 1 42
+//     out 5 84
+//     end 6
+//
+// with `#` comments and blank lines ignored.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::observer::{Observer, Verdict};
+use super::{Cell, Error, Machine, State};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Event<C> {
+    Input { step: u64, value: C },
+    Output { step: u64, value: C },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Tape<C> {
+    pub events: Vec<Event<C>>,
+    // Instructions fetched when recording stopped.
+    pub end: u64,
+}
+
+impl<C> Tape<C> {
+    pub fn inputs(&self) -> impl Iterator<Item = &C> {
+        self.events.iter().filter_map(|e| match e {
+            Event::Input { value, .. } => Some(value),
+            Event::Output { .. } => None,
+        })
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &C> {
+        self.events.iter().filter_map(|e| match e {
+            Event::Output { value, .. } => Some(value),
+            Event::Input { .. } => None,
+        })
+    }
+}
+
+impl<C: fmt::Display> fmt::Display for Tape<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for event in &self.events {
+            match event {
+                Event::Input { step, value } => writeln!(f, "in {} {}", step, value)?,
+                Event::Output { step, value } => writeln!(f, "out {} {}", step, value)?,
+            }
+        }
+        writeln!(f, "end {}", self.end)
+    }
+}
+
+#[derive(Debug)]
+pub enum TapeError {
+    Io(std::io::Error),
+    // Lines are 1-based.
+    InvalidLine { line: usize, text: String },
+    MissingEnd,
+}
+
+impl fmt::Display for TapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TapeError::Io(e) => write!(f, "failed to access replay file: {}", e),
+            TapeError::InvalidLine { line, text } => {
+                write!(f, "invalid replay line {}: {:?}", line, text)
+            }
+            TapeError::MissingEnd => write!(f, "replay has no end line"),
+        }
+    }
+}
+
+impl std::error::Error for TapeError {}
+
+impl From<std::io::Error> for TapeError {
+    fn from(e: std::io::Error) -> Self {
+        TapeError::Io(e)
+    }
+}
+
+impl<C: FromStr> FromStr for Tape<C> {
+    type Err = TapeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut events = vec![];
+        let mut end = None;
+
+        for (i, line) in s.lines().enumerate() {
+            let text = line.split('#').next().unwrap_or_default().trim();
+            if text.is_empty() {
+                continue;
+            }
+            let invalid = || TapeError::InvalidLine {
+                line: i + 1,
+                text: line.to_string(),
+            };
+            if end.is_some() {
+                return Err(invalid());
+            }
+
+            let words: Vec<&str> = text.split_whitespace().collect();
+            let step = words
+                .get(1)
+                .and_then(|x| x.parse().ok())
+                .ok_or_else(invalid)?;
+            let value = || {
+                words
+                    .get(2)
+                    .and_then(|x| x.parse().ok())
+                    .ok_or_else(invalid)
+            };
+            match (words[0], words.len()) {
+                ("in", 3) => events.push(Event::Input {
+                    step,
+                    value: value()?,
+                }),
+                ("out", 3) => events.push(Event::Output {
+                    step,
+                    value: value()?,
+                }),
+                ("end", 2) => end = Some(step),
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(Tape {
+            events,
+            end: end.ok_or(TapeError::MissingEnd)?,
+        })
+    }
+}
+
+impl<C: FromStr + fmt::Display> Tape<C> {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), TapeError> {
+        Ok(std::fs::write(path, self.to_string())?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TapeError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+}
+
+// Records a session. Instructions vetoed by another observer are recorded
+// again when they're retried, so record without vetoing observers.
+#[derive(Debug)]
+pub struct Recorder<C> {
+    events: Vec<Event<C>>,
+    steps: u64,
+    limit: Option<u64>,
+}
+
+impl<C> Default for Recorder<C> {
+    fn default() -> Self {
+        Recorder {
+            events: vec![],
+            steps: 0,
+            limit: None,
+        }
+    }
+}
+
+impl<C> Recorder<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn finish(self) -> Tape<C> {
+        Tape {
+            events: self.events,
+            end: self.steps,
+        }
+    }
+}
+
+impl<C: Clone> Observer<C> for Recorder<C> {
+    fn on_fetch(&mut self, _ip: usize, _instruction: &mut C) -> Verdict {
+        if Some(self.steps) == self.limit {
+            return Verdict::Veto;
+        }
+        self.steps += 1;
+        Verdict::Allow
+    }
+
+    fn on_input(&mut self, _ip: usize, value: &mut C) -> Verdict {
+        self.events.push(Event::Input {
+            step: self.steps,
+            value: value.clone(),
+        });
+        Verdict::Allow
+    }
+
+    fn on_output(&mut self, _ip: usize, value: &mut C) -> Verdict {
+        self.events.push(Event::Output {
+            step: self.steps,
+            value: value.clone(),
+        });
+        Verdict::Allow
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReplayError<C> {
+    Machine(Error<C>),
+    // The first event that differs from the tape. `None` means the tape or
+    // the replay ran out of events first.
+    Diverged {
+        index: usize,
+        expected: Option<Event<C>>,
+        actual: Option<Event<C>>,
+    },
+}
+
+impl<C: fmt::Debug + fmt::Display> fmt::Display for ReplayError<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Machine(e) => write!(f, "replay failed: {}", e),
+            ReplayError::Diverged {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "replay diverged at event {}: expected {:?}, got {:?}",
+                index, expected, actual
+            ),
+        }
+    }
+}
+
+impl<C: fmt::Debug + fmt::Display> std::error::Error for ReplayError<C> {}
+
+// Runs `machine` from the start of the recorded session, feeding it the
+// tape's inputs as it asks for them, and checks that it consumes and produces
+// the same values at the same steps.
+pub fn replay<C: Cell>(mut machine: Machine<C>, tape: &Tape<C>) -> Result<(), ReplayError<C>> {
+    let mut recorder = Recorder {
+        limit: Some(tape.end),
+        ..Recorder::new()
+    };
+    let mut inputs = tape.inputs().cloned();
+
+    loop {
+        match machine.run_with(&mut recorder) {
+            Ok(State::NeedsInput) => match inputs.next() {
+                Some(value) => machine.push_input(value),
+                None => break,
+            },
+            Ok(State::Output(_)) => {}
+            Ok(State::Halted | State::Vetoed) => break,
+            Err(e) => return Err(ReplayError::Machine(e)),
+        }
+    }
+
+    let actual = recorder.finish();
+    let len = tape.events.len().max(actual.events.len());
+    for index in 0..len {
+        let expected = tape.events.get(index);
+        let event = actual.events.get(index);
+        if expected != event {
+            return Err(ReplayError::Diverged {
+                index,
+                expected: expected.cloned(),
+                actual: event.cloned(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Outputs the running total of its inputs until it reads a zero.
+    const TOTALS: [i64; 17] = [
+        3, 16, 1006, 16, 14, 1, 15, 16, 15, 4, 15, 1105, 1, 0, 99, 0, 0,
+    ];
+
+    fn session(inputs: &[i64]) -> Tape<i64> {
+        let mut machine = Machine::new(TOTALS.to_vec());
+        let mut recorder = Recorder::new();
+        let mut inputs = inputs.iter();
+        loop {
+            match machine.run_with(&mut recorder).unwrap() {
+                State::NeedsInput => match inputs.next() {
+                    Some(&x) => machine.push_input(x),
+                    None => break,
+                },
+                State::Output(_) => {}
+                _ => break,
+            }
+        }
+        recorder.finish()
+    }
+
+    #[test]
+    fn records_inputs_and_outputs_with_steps() {
+        let tape = session(&[3, 4]);
+        assert_eq!(
+            tape.events,
+            [
+                Event::Input { step: 1, value: 3 },
+                Event::Output { step: 4, value: 3 },
+                Event::Input { step: 6, value: 4 },
+                Event::Output { step: 9, value: 7 },
+            ]
+        );
+        assert_eq!(tape.end, 10);
+        assert_eq!(tape.inputs().collect::<Vec<_>>(), [&3, &4]);
+        assert_eq!(tape.outputs().collect::<Vec<_>>(), [&3, &7]);
+    }
+
+    #[test]
+    fn saves_and_loads_tapes() {
+        let tape = session(&[3, 4, 0]);
+        let path = std::env::temp_dir().join(format!("intcode-replay-{}.txt", std::process::id()));
+        tape.save(&path).unwrap();
+        let loaded = Tape::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, tape);
+
+        let text = "# a bug report\nin 1 3\n\nout 4 3 # first total\nend 5\n";
+        let tape: Tape<i64> = text.parse().unwrap();
+        assert_eq!(tape.events.len(), 2);
+        assert_eq!(tape.end, 5);
+    }
+
+    #[test]
+    fn rejects_malformed_tapes() {
+        for (text, line) in [
+            ("in 1\nend 2", 1),
+            ("in 1 2\nout x 3", 2),
+            ("end 1\nin 2 3", 2),
+        ] {
+            match text.parse::<Tape<i64>>() {
+                Err(TapeError::InvalidLine { line: l, .. }) => assert_eq!(l, line, "{}", text),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert!(matches!(
+            "in 1 2".parse::<Tape<i64>>(),
+            Err(TapeError::MissingEnd)
+        ));
+    }
+
+    #[test]
+    fn replays_sessions() {
+        for inputs in [&[][..], &[5], &[3, 4], &[3, 4, 0]] {
+            let tape = session(inputs);
+            assert_eq!(replay(Machine::new(TOTALS.to_vec()), &tape), Ok(()));
+        }
+    }
+
+    #[test]
+    fn stops_where_the_recording_did() {
+        let mut tape = session(&[3, 4]);
+        tape.events.truncate(1);
+        tape.end = 3;
+        assert_eq!(replay(Machine::new(TOTALS.to_vec()), &tape), Ok(()));
+    }
+
+    #[test]
+    fn reports_divergence() {
+        let tape = session(&[3, 4, 0]);
+
+        let mut changed = tape.clone();
+        changed.events[3] = Event::Output { step: 9, value: 8 };
+        assert_eq!(
+            replay(Machine::new(TOTALS.to_vec()), &changed),
+            Err(ReplayError::Diverged {
+                index: 3,
+                expected: Some(Event::Output { step: 9, value: 8 }),
+                actual: Some(Event::Output { step: 9, value: 7 }),
+            })
+        );
+
+        // A different program consumes the same inputs at different steps.
+        let mut patched = TOTALS.to_vec();
+        patched[13] = 2;
+        assert_eq!(
+            replay(Machine::new(patched), &tape),
+            Err(ReplayError::Diverged {
+                index: 2,
+                expected: Some(Event::Input { step: 6, value: 4 }),
+                actual: Some(Event::Output { step: 8, value: 6 }),
+            })
+        );
+    }
+}
@@ -0,0 +1,535 @@
+// Symbolic execution of Intcode programs. Cells hold expressions over input
+// variables instead of numbers; jumps on symbolic conditions fork the run and
+// record the branch taken as a path constraint.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use super::Error;
+use crate::day_five::{decode, Mode, OpCode};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Const(i64),
+    Var(usize),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    LessThan(Box<Expr>, Box<Expr>),
+    Equals(Box<Expr>, Box<Expr>),
+    // A value read through a symbolic address by the instruction at this ip.
+    Unknown(usize),
+}
+
+// constant + sum(coefficient * Var(index))
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Linear {
+    pub constant: i64,
+    pub coefficients: BTreeMap<usize, i64>,
+}
+
+impl Expr {
+    pub fn checked_add(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a, b) {
+            (Expr::Const(x), Expr::Const(y)) => Expr::Const(x.checked_add(y)?),
+            (Expr::Const(0), e) | (e, Expr::Const(0)) => e,
+            (a, b) => Expr::Add(Box::new(a), Box::new(b)),
+        })
+    }
+
+    pub fn checked_mul(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a, b) {
+            (Expr::Const(x), Expr::Const(y)) => Expr::Const(x.checked_mul(y)?),
+            (Expr::Const(0), _) | (_, Expr::Const(0)) => Expr::Const(0),
+            (Expr::Const(1), e) | (e, Expr::Const(1)) => e,
+            (a, b) => Expr::Mul(Box::new(a), Box::new(b)),
+        })
+    }
+
+    pub fn less_than(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(x), Expr::Const(y)) => Expr::Const((x < y) as i64),
+            (a, b) => Expr::LessThan(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn equals(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(x), Expr::Const(y)) => Expr::Const((x == y) as i64),
+            (a, b) if a == b && !a.has_unknown() => Expr::Const(1),
+            (a, b) => Expr::Equals(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn has_unknown(&self) -> bool {
+        match self {
+            Expr::Const(_) | Expr::Var(_) => false,
+            Expr::Unknown(_) => true,
+            Expr::Add(a, b) | Expr::Mul(a, b) | Expr::LessThan(a, b) | Expr::Equals(a, b) => {
+                a.has_unknown() || b.has_unknown()
+            }
+        }
+    }
+
+    pub fn vars(&self) -> Vec<usize> {
+        let mut vars = match self {
+            Expr::Var(v) => vec![*v],
+            Expr::Const(_) | Expr::Unknown(_) => vec![],
+            Expr::Add(a, b) | Expr::Mul(a, b) | Expr::LessThan(a, b) | Expr::Equals(a, b) => {
+                let mut vars = a.vars();
+                vars.extend(b.vars());
+                vars
+            }
+        };
+        vars.sort_unstable();
+        vars.dedup();
+        vars
+    }
+
+    pub fn eval(&self, vars: &[i64]) -> Option<i64> {
+        match self {
+            Expr::Const(x) => Some(*x),
+            Expr::Var(v) => vars.get(*v).copied(),
+            Expr::Add(a, b) => a.eval(vars)?.checked_add(b.eval(vars)?),
+            Expr::Mul(a, b) => a.eval(vars)?.checked_mul(b.eval(vars)?),
+            Expr::LessThan(a, b) => Some((a.eval(vars)? < b.eval(vars)?) as i64),
+            Expr::Equals(a, b) => Some((a.eval(vars)? == b.eval(vars)?) as i64),
+            Expr::Unknown(_) => None,
+        }
+    }
+
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(x) => Some(Linear {
+                constant: *x,
+                ..Linear::default()
+            }),
+            Expr::Var(v) => Some(Linear {
+                constant: 0,
+                coefficients: BTreeMap::from([(*v, 1)]),
+            }),
+            Expr::Add(a, b) => {
+                let (mut a, b) = (a.linear()?, b.linear()?);
+                a.constant = a.constant.checked_add(b.constant)?;
+                for (v, c) in b.coefficients {
+                    let entry = a.coefficients.entry(v).or_insert(0);
+                    *entry = entry.checked_add(c)?;
+                }
+                a.coefficients.retain(|_, c| *c != 0);
+                Some(a)
+            }
+            Expr::Mul(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                let (mut scaled, factor) =
+                    match (a.coefficients.is_empty(), b.coefficients.is_empty()) {
+                        (_, true) => (a, b.constant),
+                        (true, false) => (b, a.constant),
+                        (false, false) => return None,
+                    };
+                scaled.constant = scaled.constant.checked_mul(factor)?;
+                for c in scaled.coefficients.values_mut() {
+                    *c = c.checked_mul(factor)?;
+                }
+                scaled.coefficients.retain(|_, c| *c != 0);
+                Some(scaled)
+            }
+            Expr::LessThan(..) | Expr::Equals(..) | Expr::Unknown(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Const(x) => write!(f, "{}", x),
+            Expr::Var(v) => write!(f, "v{}", v),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "({} * {})", a, b),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equals(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Unknown(ip) => write!(f, "?{}", ip),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum End {
+    Halted,
+    Failed(Error<Expr>),
+    StepLimit,
+    // Forking here would have gone over the limit on paths.
+    PathLimit,
+}
+
+#[derive(Clone, Debug)]
+pub struct Path {
+    // Each condition was taken as non-zero (true) or zero (false).
+    pub constraints: Vec<(Expr, bool)>,
+    pub outputs: Vec<Expr>,
+    pub memory: Vec<Expr>,
+    pub end: End,
+    ip: usize,
+    base: i64,
+    next_var: usize,
+    steps: usize,
+}
+
+// Replaces the given cells with variables v0, v1, ... in order.
+pub fn symbolize(ops: &[i64], cells: &[usize]) -> Vec<Expr> {
+    let mut memory: Vec<Expr> = ops.iter().map(|x| Expr::Const(*x)).collect();
+    for (var, cell) in cells.iter().enumerate() {
+        memory[*cell] = Expr::Var(var);
+    }
+    memory
+}
+
+// Explores every path through the program, forking on symbolic jumps. Each
+// Input instruction introduces a fresh variable after those already in memory.
+// At most `max_paths` paths are returned, each of at most `max_steps` steps.
+pub fn explore(memory: Vec<Expr>, max_steps: usize, max_paths: usize) -> Vec<Path> {
+    let next_var = memory
+        .iter()
+        .flat_map(|e| e.vars())
+        .max()
+        .map_or(0, |v| v + 1);
+    let mut pending = vec![Path {
+        constraints: vec![],
+        outputs: vec![],
+        memory,
+        end: End::Halted,
+        ip: 0,
+        base: 0,
+        next_var,
+        steps: 0,
+    }];
+    let mut finished = vec![];
+
+    while let Some(mut path) = pending.pop() {
+        loop {
+            if path.steps == max_steps {
+                path.end = End::StepLimit;
+                break;
+            }
+            path.steps += 1;
+            match path.step() {
+                Ok(Step::Continue) => {}
+                Ok(Step::Halt) => {
+                    path.end = End::Halted;
+                    break;
+                }
+                Ok(Step::Fork(_, _, _)) if finished.len() + pending.len() + 2 > max_paths => {
+                    path.end = End::PathLimit;
+                    break;
+                }
+                Ok(Step::Fork(cond, target, fallthrough)) => {
+                    let mut other = path.clone();
+                    other.constraints.push((cond.clone(), false));
+                    other.ip = fallthrough;
+                    pending.push(other);
+                    path.constraints.push((cond, true));
+                    path.ip = target;
+                }
+                Err(e) => {
+                    path.end = End::Failed(e);
+                    break;
+                }
+            }
+        }
+        finished.push(path);
+    }
+
+    finished
+}
+
+enum Step {
+    Continue,
+    Halt,
+    // Condition, ip when non-zero, ip when zero.
+    Fork(Expr, usize, usize),
+}
+
+impl Path {
+    fn read(&self, address: usize) -> Result<Expr, Error<Expr>> {
+        self.memory
+            .get(address)
+            .cloned()
+            .ok_or(Error::InvalidAddress {
+                ip: self.ip,
+                address: Expr::Const(address as i64),
+            })
+    }
+
+    fn address(&self, value: Expr) -> Result<usize, Error<Expr>> {
+        match value {
+            Expr::Const(x) if x >= 0 && (x as usize) < self.memory.len() => Ok(x as usize),
+            address => Err(Error::InvalidAddress {
+                ip: self.ip,
+                address,
+            }),
+        }
+    }
+
+    fn operand_address(&self, offset: usize, mode: Mode) -> Result<Expr, Error<Expr>> {
+        let arg = self.read(self.ip + offset)?;
+        match mode {
+            Mode::Relative => Expr::checked_add(arg, Expr::Const(self.base))
+                .ok_or(Error::Overflow { ip: self.ip }),
+            Mode::Position | Mode::Immediate => Ok(arg),
+        }
+    }
+
+    fn get_val(&self, offset: usize, mode: Mode) -> Result<Expr, Error<Expr>> {
+        if mode == Mode::Immediate {
+            return self.read(self.ip + offset);
+        }
+        match self.operand_address(offset, mode)? {
+            Expr::Const(x) => self.read(self.address(Expr::Const(x))?),
+            _ => Ok(Expr::Unknown(self.ip)),
+        }
+    }
+
+    fn write(&mut self, offset: usize, mode: Mode, value: Expr) -> Result<(), Error<Expr>> {
+        let target = self.address(self.operand_address(offset, mode)?)?;
+        self.memory[target] = value;
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<Step, Error<Expr>> {
+        let instruction = self.read(self.ip)?;
+        let (mode3, mode2, mode1, op) = match instruction {
+            Expr::Const(x) => usize::try_from(x).ok().and_then(decode),
+            _ => None,
+        }
+        .ok_or(Error::InvalidOpCode {
+            ip: self.ip,
+            value: instruction,
+        })?;
+        let overflow = Error::Overflow { ip: self.ip };
+
+        match op {
+            OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equals => {
+                let arg1 = self.get_val(1, mode1)?;
+                let arg2 = self.get_val(2, mode2)?;
+                let result = match op {
+                    OpCode::Add => Expr::checked_add(arg1, arg2).ok_or(overflow)?,
+                    OpCode::Mul => Expr::checked_mul(arg1, arg2).ok_or(overflow)?,
+                    OpCode::LessThan => Expr::less_than(arg1, arg2),
+                    _ => Expr::equals(arg1, arg2),
+                };
+                self.write(3, mode3, result)?;
+                self.ip += 4;
+            }
+            OpCode::Input => {
+                self.write(1, mode1, Expr::Var(self.next_var))?;
+                self.next_var += 1;
+                self.ip += 2;
+            }
+            OpCode::Out => {
+                let arg = self.get_val(1, mode1)?;
+                self.outputs.push(arg);
+                self.ip += 2;
+            }
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let cond = self.get_val(1, mode1)?;
+                let target = self.address(self.get_val(2, mode2)?)?;
+                let fallthrough = self.ip + 3;
+                let (when_true, when_false) = if op == OpCode::JumpIfTrue {
+                    (target, fallthrough)
+                } else {
+                    (fallthrough, target)
+                };
+                match cond {
+                    Expr::Const(0) => self.ip = when_false,
+                    Expr::Const(_) => self.ip = when_true,
+                    cond => return Ok(Step::Fork(cond, when_true, when_false)),
+                }
+            }
+            OpCode::AdjustRelativeBase => match self.get_val(1, mode1)? {
+                Expr::Const(x) => {
+                    self.base = self.base.checked_add(x).ok_or(overflow)?;
+                    self.ip += 2;
+                }
+                address => {
+                    return Err(Error::InvalidAddress {
+                        ip: self.ip,
+                        address,
+                    })
+                }
+            },
+            OpCode::Halt => return Ok(Step::Halt),
+        }
+
+        Ok(Step::Continue)
+    }
+
+    pub fn satisfied_by(&self, vars: &[i64]) -> bool {
+        self.constraints
+            .iter()
+            .all(|(cond, taken)| cond.eval(vars).map(|x| x != 0) == Some(*taken))
+    }
+
+    // Finds values for the variables, each within its domain, such that the
+    // cell ends up equal to the target and every path constraint holds. When
+    // the cell is linear the last variable is solved for instead of searched.
+    pub fn solve(
+        &self,
+        cell: usize,
+        target: i64,
+        domains: &[RangeInclusive<i64>],
+    ) -> Option<Vec<i64>> {
+        if self.end != End::Halted {
+            return None;
+        }
+        let expr = self.memory.get(cell)?;
+        let solved = expr.linear().and_then(|linear| {
+            let (var, coefficient) = linear.coefficients.iter().next_back()?;
+            Some((*var, *coefficient, linear.clone()))
+        });
+        let mut vars: Vec<i64> = domains.iter().map(|d| *d.start()).collect();
+        self.search(expr, target, domains, &solved, 0, &mut vars)
+    }
+
+    fn search(
+        &self,
+        expr: &Expr,
+        target: i64,
+        domains: &[RangeInclusive<i64>],
+        solved: &Option<(usize, i64, Linear)>,
+        index: usize,
+        vars: &mut Vec<i64>,
+    ) -> Option<Vec<i64>> {
+        if index == domains.len() {
+            let ok = expr.eval(vars) == Some(target) && self.satisfied_by(vars);
+            return if ok { Some(vars.clone()) } else { None };
+        }
+        if let Some((var, coefficient, linear)) = solved {
+            if *var == index {
+                // target = constant + coefficient * v + sum(other terms)
+                let mut rest = target.checked_sub(linear.constant)?;
+                for (v, c) in &linear.coefficients {
+                    if v != var {
+                        rest = rest.checked_sub(c.checked_mul(vars[*v])?)?;
+                    }
+                }
+                // Overflow here means no value of the variable works.
+                let value = rest.checked_div(*coefficient)?;
+                if rest.checked_rem(*coefficient)? != 0 || !domains[index].contains(&value) {
+                    return None;
+                }
+                vars[index] = value;
+                return self.search(expr, target, domains, solved, index + 1, vars);
+            }
+        }
+        for value in domains[index].clone() {
+            vars[index] = value;
+            if let Some(found) = self.search(expr, target, domains, solved, index + 1, vars) {
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_expressions_from_inputs() {
+        // out((in * 3) + 4)
+        let ops = vec![3, 13, 1002, 13, 3, 13, 1001, 13, 4, 13, 4, 13, 99, 0];
+        let paths = explore(symbolize(&ops, &[]), 100, 100);
+
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].end, End::Halted);
+        assert_eq!(paths[0].outputs[0].to_string(), "((v0 * 3) + 4)");
+        assert_eq!(
+            paths[0].outputs[0].linear(),
+            Some(Linear {
+                constant: 4,
+                coefficients: BTreeMap::from([(0, 3)])
+            })
+        );
+    }
+
+    #[test]
+    fn forks_on_symbolic_jumps() {
+        // Outputs 1 when the input equals 8, otherwise 0.
+        let ops = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        let paths = explore(symbolize(&ops, &[]), 100, 100);
+
+        assert_eq!(paths.len(), 1);
+        assert_eq!(
+            paths[0].outputs,
+            vec![Expr::equals(Expr::Var(0), Expr::Const(8))]
+        );
+
+        let ops = vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let paths = explore(symbolize(&ops, &[]), 100, 100);
+        assert_eq!(paths.len(), 2);
+        for path in &paths {
+            assert_eq!(path.end, End::Halted);
+            let (cond, taken) = &path.constraints[0];
+            assert_eq!(*cond, Expr::Var(0));
+            let expected = if *taken { 1 } else { 0 };
+            assert_eq!(path.outputs, vec![Expr::Const(expected)]);
+        }
+    }
+
+    #[test]
+    fn solves_linear_target() {
+        // ops[0] = v0 * 5 + v1 + 7
+        let ops = vec![1102, 0, 5, 13, 1001, 13, 0, 0, 1001, 0, 7, 0, 99, 0];
+        let memory = symbolize(&ops, &[1, 6]);
+        let paths = explore(memory, 100, 100);
+        let found = paths[0].solve(0, 234, &[0..=99, 0..=99]);
+        // The smallest v0 with a v1 in range: 26 * 5 + 97 + 7 == 234
+        assert_eq!(found, Some(vec![26, 97]));
+        assert_eq!(
+            paths[0].solve(0, 234, &[30..=30, 0..=99]),
+            Some(vec![30, 77])
+        );
+        assert_eq!(paths[0].solve(0, -1, &[0..=99, 0..=99]), None);
+
+        // ops[0] = v0 * -1, which can't be i64::MIN.
+        let ops = vec![1002, 5, -1, 0, 99, 0];
+        let paths = explore(symbolize(&ops, &[5]), 100, 100);
+        let all = [i64::MIN..=i64::MAX];
+        assert_eq!(paths[0].solve(0, i64::MIN, &all), None);
+        assert_eq!(paths[0].solve(0, i64::MAX, &all), Some(vec![-i64::MAX]));
+    }
+
+    #[test]
+    fn marks_symbolic_addresses() {
+        let ops = vec![1, 0, 0, 5, 99, 0];
+        let paths = explore(symbolize(&ops, &[1]), 100, 100);
+        assert_eq!(paths[0].memory[5].to_string(), "(?0 + 1)");
+
+        let ops = vec![1101, 1, 1, 0, 99];
+        let paths = explore(symbolize(&ops, &[3]), 100, 100);
+        assert_eq!(
+            paths[0].end,
+            End::Failed(Error::InvalidAddress {
+                ip: 0,
+                address: Expr::Var(0)
+            })
+        );
+    }
+
+    #[test]
+    fn stops_at_step_limit() {
+        let ops = vec![1105, 1, 0];
+        let paths = explore(symbolize(&ops, &[]), 10, 100);
+        assert_eq!(paths[0].end, End::StepLimit);
+    }
+
+    #[test]
+    fn stops_at_path_limit() {
+        // Reads inputs until one is zero, forking on each.
+        let ops = vec![3, 6, 1005, 6, 0, 99, 0];
+        let paths = explore(symbolize(&ops, &[]), 1000, 3);
+        let ends: Vec<&End> = paths.iter().map(|path| &path.end).collect();
+        assert_eq!(ends, [&End::PathLimit, &End::Halted, &End::Halted]);
+        assert_eq!(explore(symbolize(&ops, &[]), 1000, 1).len(), 1);
+    }
+}
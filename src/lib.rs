@@ -1,8 +1,18 @@
-mod day_five;
+pub mod day_eleven;
+pub mod day_fifteen;
+pub mod day_five;
 mod day_four;
-mod day_one;
+pub mod day_nine;
+pub mod day_nineteen;
+pub mod day_one;
+pub mod day_seventeen;
+pub mod day_thirteen;
 mod day_three;
-mod day_two;
+pub mod day_twenty_five;
+pub mod day_twenty_one;
+pub mod day_two;
+pub mod geometry;
+pub mod intcode;
 
 #[cfg(test)]
 mod tests {